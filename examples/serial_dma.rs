@@ -25,7 +25,7 @@ fn main() -> ! {
         gpioa.pa9.into_af7(&mut gpioa.moder, &mut gpioa.afrh),
         gpioa.pa10.into_af7(&mut gpioa.moder, &mut gpioa.afrh),
     );
-    let serial = Serial::usart1(dp.USART1, pins, 9600.bps(), clocks, &mut rcc.apb2);
+    let serial = Serial::usart1(dp.USART1, pins, 9600.bps(), clocks, &mut rcc.apb2).unwrap();
     let (tx, rx) = serial.split();
 
     let dma1 = dp.DMA1.split(&mut rcc.ahb);
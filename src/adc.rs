@@ -8,26 +8,48 @@ use crate::{
     gpio::Analog,
     rcc::{Clocks, AHB},
 };
+use core::marker::PhantomData;
 use cortex_m::asm;
 use embedded_hal::adc::{Channel, OneShot};
 
+use crate::dma;
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303",
+    feature = "stm32f334",
+    feature = "stm32f328",
+    feature = "stm32f358",
+    feature = "stm32f398",
+))]
+use crate::pac::ADC2;
 use crate::{
     gpio::{gpioa, gpiob, gpioc},
-    pac::{ADC1, ADC1_2, ADC2},
+    pac::{adc1::cfgr::ALIGN_A, adc1_2::ccr::CKMODE_A, ADC1, ADC1_2},
 };
-use stm32f3::stm32f303::{adc1::cfgr::ALIGN_A, adc1_2::ccr::CKMODE_A};
+
+mod internal;
+
 const MAX_ADVREGEN_STARTUP_US: u32 = 10;
 
 #[cfg(any(
+    feature = "stm32f302",
     feature = "stm32f303xb",
     feature = "stm32f303xc",
     feature = "stm32f303xd",
     feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398",
 ))]
-use crate::{
-    gpio::{gpiod, gpioe, gpiof},
-    pac::{ADC3, ADC3_4, ADC4},
-};
+use crate::gpio::{gpiod, gpioe, gpiof};
+#[cfg(any(
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398",
+))]
+use crate::pac::{ADC3, ADC3_4, ADC4};
 
 /// ADC configuration
 // TODO: Remove `pub` from the register block once all functionalities are implemented.
@@ -45,6 +67,7 @@ pub struct Adc<ADC> {
 /// There is always an overhead of 13 ADC clock cycles.
 /// E.g. For Sampletime T_19 the total conversion time (in ADC clock cycles) is
 /// 13 + 19 = 32 ADC Clock Cycles
+#[derive(Clone, Copy)]
 pub enum SampleTime {
     T_1,
     T_2,
@@ -81,9 +104,279 @@ impl SampleTime {
 
 #[derive(Clone, Copy, PartialEq)]
 /// ADC operation mode
-// TODO: Implement other modes (DMA, Differential,…)
 pub enum OperationMode {
     OneShot,
+    /// A sequence of channels set up by `setup_sequence`
+    Sequence(ConversionMode),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+/// How a sequence of channels is converted
+pub enum ConversionMode {
+    /// Every start converts the whole sequence once
+    Single,
+    /// The sequence is converted over and over again, until the conversion is stopped
+    Continuous,
+    /// Every start converts the next `n` channels of the sequence, with `n` in 1..=8
+    Discontinuous(u8),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+/// ADC DMA mode
+pub enum DmaMode {
+    /// The DMA requests stop after the buffer has been filled
+    OneShot,
+    /// The DMA requests go on after the buffer has been filled, which
+    /// requires a DMA channel in circular mode
+    Circular,
+}
+
+/// A sequence of up to 16 channels of an ADC
+///
+/// Channels can be added multiple times. The sample time is configured per
+/// channel, so if a channel is added multiple times, the last sample time is
+/// used for every conversion of that channel.
+///
+/// ```
+/// let sequence = adc::Sequence::new()
+///     .add(&pa0, SampleTime::T_19)
+///     .add(&pa1, SampleTime::T_19)
+///     .add(&pa2, SampleTime::T_61);
+/// adc1.setup_sequence(&sequence, ConversionMode::Continuous);
+/// ```
+pub struct Sequence<ADC> {
+    channels: [u8; 16],
+    sample_times: [SampleTime; 16],
+    len: usize,
+    _adc: PhantomData<ADC>,
+}
+
+impl<ADC> Sequence<ADC> {
+    /// Creates an empty sequence
+    pub fn new() -> Self {
+        Sequence {
+            channels: [0; 16],
+            sample_times: [SampleTime::default(); 16],
+            len: 0,
+            _adc: PhantomData,
+        }
+    }
+
+    /// Appends a channel to the sequence
+    ///
+    /// # Panics
+    ///
+    /// Panics if the sequence already contains 16 channels.
+    pub fn add<PIN>(mut self, _pin: &PIN, sample_time: SampleTime) -> Self
+    where
+        PIN: Channel<ADC, ID = u8>,
+    {
+        assert!(self.len < 16, "ADC sequence length must be in 1..=16");
+        self.channels[self.len] = PIN::channel();
+        self.sample_times[self.len] = sample_time;
+        self.len += 1;
+        self
+    }
+
+    /// Returns the number of channels in the sequence
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true, if no channel has been added yet
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<ADC> Default for Sequence<ADC> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A sequence of up to 4 channels of the injected group of an ADC
+///
+/// Injected conversions interrupt a running regular sequence, which is
+/// resumed afterwards. The results are kept in a separate data register
+/// per rank.
+///
+/// ```
+/// let sequence = adc::InjectedSequence::new()
+///     .add(&pa0, SampleTime::T_19)
+///     .add(&pa1, SampleTime::T_19);
+/// adc1.setup_injected(&sequence, Some((Adc12InjectedTrigger::Tim1Trgo, TriggerEdge::Rising)));
+/// adc1.start_injected();
+/// ```
+pub struct InjectedSequence<ADC> {
+    channels: [u8; 4],
+    sample_times: [SampleTime; 4],
+    len: usize,
+    _adc: PhantomData<ADC>,
+}
+
+impl<ADC> InjectedSequence<ADC> {
+    /// Creates an empty injected sequence
+    pub fn new() -> Self {
+        InjectedSequence {
+            channels: [0; 4],
+            sample_times: [SampleTime::default(); 4],
+            len: 0,
+            _adc: PhantomData,
+        }
+    }
+
+    /// Appends a channel to the injected sequence
+    ///
+    /// # Panics
+    ///
+    /// Panics if the sequence already contains 4 channels.
+    pub fn add<PIN>(mut self, _pin: &PIN, sample_time: SampleTime) -> Self
+    where
+        PIN: Channel<ADC, ID = u8>,
+    {
+        assert!(
+            self.len < 4,
+            "ADC injected sequence length must be in 1..=4"
+        );
+        self.channels[self.len] = PIN::channel();
+        self.sample_times[self.len] = sample_time;
+        self.len += 1;
+        self
+    }
+
+    /// Returns the number of channels in the injected sequence
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true, if no channel has been added yet
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<ADC> Default for InjectedSequence<ADC> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// One of the three analog watchdogs of an ADC
+#[derive(Clone, Copy, PartialEq)]
+pub enum AnalogWatchdog {
+    /// Analog watchdog 1, which guards one or all channels with 12 bit thresholds
+    Awd1,
+    /// Analog watchdog 2, which guards any channels with 8 bit thresholds
+    Awd2,
+    /// Analog watchdog 3, which guards any channels with 8 bit thresholds
+    Awd3,
+}
+
+/// Configuration of an analog watchdog
+///
+/// The thresholds are given with 12 bit resolution. The analog watchdogs 2
+/// and 3 only compare the 8 most significant bits.
+///
+/// ```
+/// let watchdog = adc::Watchdog::new(500, 3500).channel(&pa0);
+/// adc1.set_watchdog(AnalogWatchdog::Awd1, Some(&watchdog));
+/// adc1.listen(Event::Watchdog1);
+/// ```
+pub struct Watchdog<ADC> {
+    low: u16,
+    high: u16,
+    /// `None` guards all channels
+    channels: Option<u32>,
+    regular: bool,
+    injected: bool,
+    _adc: PhantomData<ADC>,
+}
+
+impl<ADC> Watchdog<ADC> {
+    /// Creates a watchdog configuration guarding all channels
+    pub fn new(low: u16, high: u16) -> Self {
+        Watchdog {
+            low,
+            high,
+            channels: None,
+            regular: true,
+            injected: true,
+            _adc: PhantomData,
+        }
+    }
+
+    /// Guards the channel of the pin, instead of all channels
+    ///
+    /// This can be called multiple times for the analog watchdogs 2 and 3.
+    pub fn channel<PIN>(mut self, _pin: &PIN) -> Self
+    where
+        PIN: Channel<ADC, ID = u8>,
+    {
+        self.channels = Some(self.channels.unwrap_or(0) | 1 << PIN::channel());
+        self
+    }
+
+    /// Selects, whether regular and injected conversions are guarded
+    ///
+    /// This only applies to the analog watchdog 1, the others always guard both.
+    pub fn groups(mut self, regular: bool, injected: bool) -> Self {
+        self.regular = regular;
+        self.injected = injected;
+        self
+    }
+}
+
+/// Calibration factors of an ADC
+///
+/// The calibration factors are specific to the ADC instance and the analog
+/// supply voltage they were determined with.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CalibrationFactor {
+    /// Factor applied to single ended conversions
+    pub single_ended: u8,
+    /// Factor applied to differential conversions
+    pub differential: u8,
+}
+
+/// ADC interrupt events
+#[derive(Clone, Copy, PartialEq)]
+pub enum Event {
+    /// A regular conversion has been completed
+    EndOfConversion,
+    /// The regular sequence has been completed
+    EndOfSequence,
+    /// A regular result has been overwritten before it was read
+    Overrun,
+    /// An injected conversion has been completed
+    InjectedEndOfConversion,
+    /// The injected sequence has been completed
+    InjectedEndOfSequence,
+    /// An injected context has been written, while the queue was full
+    InjectedQueueOverflow,
+    /// A result guarded by analog watchdog 1 was outside of the thresholds
+    Watchdog1,
+    /// A result guarded by analog watchdog 2 was outside of the thresholds
+    Watchdog2,
+    /// A result guarded by analog watchdog 3 was outside of the thresholds
+    Watchdog3,
+}
+
+impl Event {
+    /// Bit in the ISR and IER registers
+    fn mask(self) -> u32 {
+        match self {
+            Event::EndOfConversion => 1 << 2,
+            Event::EndOfSequence => 1 << 3,
+            Event::Overrun => 1 << 4,
+            Event::InjectedEndOfConversion => 1 << 5,
+            Event::InjectedEndOfSequence => 1 << 6,
+            Event::Watchdog1 => 1 << 7,
+            Event::Watchdog2 => 1 << 8,
+            Event::Watchdog3 => 1 << 9,
+            Event::InjectedQueueOverflow => 1 << 10,
+        }
+    }
 }
 
 #[derive(Clone, Copy, PartialEq)]
@@ -137,6 +430,546 @@ impl From<Align> for ALIGN_A {
     }
 }
 
+/// Edge of an external trigger, which starts a conversion
+#[derive(Clone, Copy, PartialEq)]
+pub enum TriggerEdge {
+    /// Rising edge
+    Rising,
+    /// Falling edge
+    Falling,
+    /// Rising and falling edge
+    Both,
+}
+
+impl TriggerEdge {
+    /// Conversion to bits for EXTEN
+    fn bitcode(self) -> u32 {
+        match self {
+            TriggerEdge::Rising => 0b01,
+            TriggerEdge::Falling => 0b10,
+            TriggerEdge::Both => 0b11,
+        }
+    }
+}
+
+/// External events of ADC1 and ADC2, which start a regular conversion
+///
+/// The timers have to be configured to output the event, e.g. the master
+/// mode selection (MMS) of a timer selects, what is output as TRGO.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Adc12Trigger {
+    /// Timer 1 capture compare 1 event
+    Tim1Cc1,
+    /// Timer 1 capture compare 2 event
+    Tim1Cc2,
+    /// Timer 1 capture compare 3 event
+    Tim1Cc3,
+    /// Timer 2 capture compare 2 event
+    Tim2Cc2,
+    /// Timer 3 TRGO event
+    #[cfg(any(
+        feature = "stm32f302",
+        feature = "stm32f303",
+        feature = "stm32f334",
+        feature = "stm32f328",
+        feature = "stm32f358",
+        feature = "stm32f398",
+    ))]
+    Tim3Trgo,
+    /// Timer 4 capture compare 4 event
+    #[cfg(any(
+        feature = "stm32f302",
+        feature = "stm32f303xb",
+        feature = "stm32f303xc",
+        feature = "stm32f303xd",
+        feature = "stm32f303xe",
+        feature = "stm32f358",
+        feature = "stm32f398",
+    ))]
+    Tim4Cc4,
+    /// EXTI line 11
+    Exti11,
+    /// Timer 8 TRGO event
+    #[cfg(any(
+        feature = "stm32f303xb",
+        feature = "stm32f303xc",
+        feature = "stm32f303xd",
+        feature = "stm32f303xe",
+        feature = "stm32f358",
+        feature = "stm32f398",
+    ))]
+    Tim8Trgo,
+    /// Timer 8 TRGO2 event
+    #[cfg(any(
+        feature = "stm32f303xb",
+        feature = "stm32f303xc",
+        feature = "stm32f303xd",
+        feature = "stm32f303xe",
+        feature = "stm32f358",
+        feature = "stm32f398",
+    ))]
+    Tim8Trgo2,
+    /// HRTIM ADC trigger 1 event
+    #[cfg(feature = "stm32f334")]
+    HrtimAdcTrg1,
+    /// HRTIM ADC trigger 3 event
+    #[cfg(feature = "stm32f334")]
+    HrtimAdcTrg3,
+    /// Timer 1 TRGO event
+    Tim1Trgo,
+    /// Timer 1 TRGO2 event
+    Tim1Trgo2,
+    /// Timer 2 TRGO event
+    Tim2Trgo,
+    /// Timer 4 TRGO event
+    #[cfg(any(
+        feature = "stm32f302",
+        feature = "stm32f303xb",
+        feature = "stm32f303xc",
+        feature = "stm32f303xd",
+        feature = "stm32f303xe",
+        feature = "stm32f358",
+        feature = "stm32f398",
+    ))]
+    Tim4Trgo,
+    /// Timer 6 TRGO event
+    Tim6Trgo,
+    /// Timer 15 TRGO event
+    Tim15Trgo,
+    /// Timer 3 capture compare 4 event
+    #[cfg(any(
+        feature = "stm32f302",
+        feature = "stm32f303",
+        feature = "stm32f334",
+        feature = "stm32f328",
+        feature = "stm32f358",
+        feature = "stm32f398",
+    ))]
+    Tim3Cc4,
+}
+
+impl Adc12Trigger {
+    /// Conversion to bits for EXTSEL
+    fn bitcode(self) -> u32 {
+        match self {
+            Adc12Trigger::Tim1Cc1 => 0,
+            Adc12Trigger::Tim1Cc2 => 1,
+            Adc12Trigger::Tim1Cc3 => 2,
+            Adc12Trigger::Tim2Cc2 => 3,
+            #[cfg(any(
+                feature = "stm32f302",
+                feature = "stm32f303",
+                feature = "stm32f334",
+                feature = "stm32f328",
+                feature = "stm32f358",
+                feature = "stm32f398",
+            ))]
+            Adc12Trigger::Tim3Trgo => 4,
+            #[cfg(any(
+                feature = "stm32f302",
+                feature = "stm32f303xb",
+                feature = "stm32f303xc",
+                feature = "stm32f303xd",
+                feature = "stm32f303xe",
+                feature = "stm32f358",
+                feature = "stm32f398",
+            ))]
+            Adc12Trigger::Tim4Cc4 => 5,
+            Adc12Trigger::Exti11 => 6,
+            #[cfg(any(
+                feature = "stm32f303xb",
+                feature = "stm32f303xc",
+                feature = "stm32f303xd",
+                feature = "stm32f303xe",
+                feature = "stm32f358",
+                feature = "stm32f398",
+            ))]
+            Adc12Trigger::Tim8Trgo => 7,
+            #[cfg(any(
+                feature = "stm32f303xb",
+                feature = "stm32f303xc",
+                feature = "stm32f303xd",
+                feature = "stm32f303xe",
+                feature = "stm32f358",
+                feature = "stm32f398",
+            ))]
+            Adc12Trigger::Tim8Trgo2 => 8,
+            #[cfg(feature = "stm32f334")]
+            Adc12Trigger::HrtimAdcTrg1 => 7,
+            #[cfg(feature = "stm32f334")]
+            Adc12Trigger::HrtimAdcTrg3 => 8,
+            Adc12Trigger::Tim1Trgo => 9,
+            Adc12Trigger::Tim1Trgo2 => 10,
+            Adc12Trigger::Tim2Trgo => 11,
+            #[cfg(any(
+                feature = "stm32f302",
+                feature = "stm32f303xb",
+                feature = "stm32f303xc",
+                feature = "stm32f303xd",
+                feature = "stm32f303xe",
+                feature = "stm32f358",
+                feature = "stm32f398",
+            ))]
+            Adc12Trigger::Tim4Trgo => 12,
+            Adc12Trigger::Tim6Trgo => 13,
+            Adc12Trigger::Tim15Trgo => 14,
+            #[cfg(any(
+                feature = "stm32f302",
+                feature = "stm32f303",
+                feature = "stm32f334",
+                feature = "stm32f328",
+                feature = "stm32f358",
+                feature = "stm32f398",
+            ))]
+            Adc12Trigger::Tim3Cc4 => 15,
+        }
+    }
+}
+
+/// External events of ADC3 and ADC4, which start a regular conversion
+///
+/// The timers have to be configured to output the event, e.g. the master
+/// mode selection (MMS) of a timer selects, what is output as TRGO.
+#[cfg(any(
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398",
+))]
+#[derive(Clone, Copy, PartialEq)]
+pub enum Adc34Trigger {
+    /// Timer 3 capture compare 1 event
+    Tim3Cc1,
+    /// Timer 2 capture compare 3 event
+    Tim2Cc3,
+    /// Timer 1 capture compare 3 event
+    Tim1Cc3,
+    /// Timer 8 capture compare 1 event
+    Tim8Cc1,
+    /// Timer 8 TRGO event
+    Tim8Trgo,
+    /// EXTI line 2
+    Exti2,
+    /// Timer 4 capture compare 1 event
+    Tim4Cc1,
+    /// Timer 2 TRGO event
+    Tim2Trgo,
+    /// Timer 8 TRGO2 event
+    Tim8Trgo2,
+    /// Timer 1 TRGO event
+    Tim1Trgo,
+    /// Timer 1 TRGO2 event
+    Tim1Trgo2,
+    /// Timer 3 TRGO event
+    Tim3Trgo,
+    /// Timer 4 TRGO event
+    Tim4Trgo,
+    /// Timer 7 TRGO event
+    Tim7Trgo,
+    /// Timer 15 TRGO event
+    Tim15Trgo,
+    /// Timer 2 capture compare 1 event
+    Tim2Cc1,
+}
+
+#[cfg(any(
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398",
+))]
+impl Adc34Trigger {
+    /// Conversion to bits for EXTSEL
+    fn bitcode(self) -> u32 {
+        self as u32
+    }
+}
+
+/// External events of ADC1 and ADC2, which start an injected conversion
+///
+/// The timers have to be configured to output the event, like for
+/// [`Adc12Trigger`](enum.Adc12Trigger.html).
+#[derive(Clone, Copy, PartialEq)]
+pub enum Adc12InjectedTrigger {
+    /// Timer 1 TRGO event
+    Tim1Trgo,
+    /// Timer 1 capture compare 4 event
+    Tim1Cc4,
+    /// Timer 2 TRGO event
+    Tim2Trgo,
+    /// Timer 2 capture compare 1 event
+    Tim2Cc1,
+    /// Timer 3 capture compare 4 event
+    #[cfg(any(
+        feature = "stm32f302",
+        feature = "stm32f303",
+        feature = "stm32f334",
+        feature = "stm32f328",
+        feature = "stm32f358",
+        feature = "stm32f398",
+    ))]
+    Tim3Cc4,
+    /// Timer 4 TRGO event
+    #[cfg(any(
+        feature = "stm32f302",
+        feature = "stm32f303xb",
+        feature = "stm32f303xc",
+        feature = "stm32f303xd",
+        feature = "stm32f303xe",
+        feature = "stm32f358",
+        feature = "stm32f398",
+    ))]
+    Tim4Trgo,
+    /// EXTI line 15
+    Exti15,
+    /// Timer 8 capture compare 4 event
+    #[cfg(any(
+        feature = "stm32f303xb",
+        feature = "stm32f303xc",
+        feature = "stm32f303xd",
+        feature = "stm32f303xe",
+        feature = "stm32f358",
+        feature = "stm32f398",
+    ))]
+    Tim8Cc4,
+    /// Timer 1 TRGO2 event
+    Tim1Trgo2,
+    /// Timer 8 TRGO event
+    #[cfg(any(
+        feature = "stm32f303xb",
+        feature = "stm32f303xc",
+        feature = "stm32f303xd",
+        feature = "stm32f303xe",
+        feature = "stm32f358",
+        feature = "stm32f398",
+    ))]
+    Tim8Trgo,
+    /// Timer 8 TRGO2 event
+    #[cfg(any(
+        feature = "stm32f303xb",
+        feature = "stm32f303xc",
+        feature = "stm32f303xd",
+        feature = "stm32f303xe",
+        feature = "stm32f358",
+        feature = "stm32f398",
+    ))]
+    Tim8Trgo2,
+    /// HRTIM ADC trigger 2 event
+    #[cfg(feature = "stm32f334")]
+    HrtimAdcTrg2,
+    /// HRTIM ADC trigger 4 event
+    #[cfg(feature = "stm32f334")]
+    HrtimAdcTrg4,
+    /// Timer 3 capture compare 3 event
+    #[cfg(any(
+        feature = "stm32f302",
+        feature = "stm32f303",
+        feature = "stm32f334",
+        feature = "stm32f328",
+        feature = "stm32f358",
+        feature = "stm32f398",
+    ))]
+    Tim3Cc3,
+    /// Timer 3 TRGO event
+    #[cfg(any(
+        feature = "stm32f302",
+        feature = "stm32f303",
+        feature = "stm32f334",
+        feature = "stm32f328",
+        feature = "stm32f358",
+        feature = "stm32f398",
+    ))]
+    Tim3Trgo,
+    /// Timer 3 capture compare 1 event
+    #[cfg(any(
+        feature = "stm32f302",
+        feature = "stm32f303",
+        feature = "stm32f334",
+        feature = "stm32f328",
+        feature = "stm32f358",
+        feature = "stm32f398",
+    ))]
+    Tim3Cc1,
+    /// Timer 6 TRGO event
+    Tim6Trgo,
+    /// Timer 15 TRGO event
+    Tim15Trgo,
+}
+
+impl Adc12InjectedTrigger {
+    /// Conversion to bits for JEXTSEL
+    fn bitcode(self) -> u32 {
+        match self {
+            Adc12InjectedTrigger::Tim1Trgo => 0,
+            Adc12InjectedTrigger::Tim1Cc4 => 1,
+            Adc12InjectedTrigger::Tim2Trgo => 2,
+            Adc12InjectedTrigger::Tim2Cc1 => 3,
+            #[cfg(any(
+                feature = "stm32f302",
+                feature = "stm32f303",
+                feature = "stm32f334",
+                feature = "stm32f328",
+                feature = "stm32f358",
+                feature = "stm32f398",
+            ))]
+            Adc12InjectedTrigger::Tim3Cc4 => 4,
+            #[cfg(any(
+                feature = "stm32f302",
+                feature = "stm32f303xb",
+                feature = "stm32f303xc",
+                feature = "stm32f303xd",
+                feature = "stm32f303xe",
+                feature = "stm32f358",
+                feature = "stm32f398",
+            ))]
+            Adc12InjectedTrigger::Tim4Trgo => 5,
+            Adc12InjectedTrigger::Exti15 => 6,
+            #[cfg(any(
+                feature = "stm32f303xb",
+                feature = "stm32f303xc",
+                feature = "stm32f303xd",
+                feature = "stm32f303xe",
+                feature = "stm32f358",
+                feature = "stm32f398",
+            ))]
+            Adc12InjectedTrigger::Tim8Cc4 => 7,
+            Adc12InjectedTrigger::Tim1Trgo2 => 8,
+            #[cfg(any(
+                feature = "stm32f303xb",
+                feature = "stm32f303xc",
+                feature = "stm32f303xd",
+                feature = "stm32f303xe",
+                feature = "stm32f358",
+                feature = "stm32f398",
+            ))]
+            Adc12InjectedTrigger::Tim8Trgo => 9,
+            #[cfg(any(
+                feature = "stm32f303xb",
+                feature = "stm32f303xc",
+                feature = "stm32f303xd",
+                feature = "stm32f303xe",
+                feature = "stm32f358",
+                feature = "stm32f398",
+            ))]
+            Adc12InjectedTrigger::Tim8Trgo2 => 10,
+            #[cfg(feature = "stm32f334")]
+            Adc12InjectedTrigger::HrtimAdcTrg2 => 9,
+            #[cfg(feature = "stm32f334")]
+            Adc12InjectedTrigger::HrtimAdcTrg4 => 10,
+            #[cfg(any(
+                feature = "stm32f302",
+                feature = "stm32f303",
+                feature = "stm32f334",
+                feature = "stm32f328",
+                feature = "stm32f358",
+                feature = "stm32f398",
+            ))]
+            Adc12InjectedTrigger::Tim3Cc3 => 11,
+            #[cfg(any(
+                feature = "stm32f302",
+                feature = "stm32f303",
+                feature = "stm32f334",
+                feature = "stm32f328",
+                feature = "stm32f358",
+                feature = "stm32f398",
+            ))]
+            Adc12InjectedTrigger::Tim3Trgo => 12,
+            #[cfg(any(
+                feature = "stm32f302",
+                feature = "stm32f303",
+                feature = "stm32f334",
+                feature = "stm32f328",
+                feature = "stm32f358",
+                feature = "stm32f398",
+            ))]
+            Adc12InjectedTrigger::Tim3Cc1 => 13,
+            Adc12InjectedTrigger::Tim6Trgo => 14,
+            Adc12InjectedTrigger::Tim15Trgo => 15,
+        }
+    }
+}
+
+/// External events of ADC3 and ADC4, which start an injected conversion
+///
+/// The timers have to be configured to output the event, like for
+/// [`Adc34Trigger`](enum.Adc34Trigger.html).
+#[cfg(any(
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398",
+))]
+#[derive(Clone, Copy, PartialEq)]
+pub enum Adc34InjectedTrigger {
+    /// Timer 1 TRGO event
+    Tim1Trgo,
+    /// Timer 1 capture compare 4 event
+    Tim1Cc4,
+    /// Timer 4 capture compare 3 event
+    Tim4Cc3,
+    /// Timer 8 capture compare 2 event
+    Tim8Cc2,
+    /// Timer 8 capture compare 4 event
+    Tim8Cc4,
+    /// Timer 4 capture compare 4 event
+    Tim4Cc4,
+    /// Timer 4 TRGO event
+    Tim4Trgo,
+    /// Timer 1 TRGO2 event
+    Tim1Trgo2,
+    /// Timer 8 TRGO event
+    Tim8Trgo,
+    /// Timer 8 TRGO2 event
+    Tim8Trgo2,
+    /// Timer 1 capture compare 3 event
+    Tim1Cc3,
+    /// Timer 3 TRGO event
+    Tim3Trgo,
+    /// Timer 2 TRGO event
+    Tim2Trgo,
+    /// Timer 7 TRGO event
+    Tim7Trgo,
+    /// Timer 15 TRGO event
+    Tim15Trgo,
+}
+
+#[cfg(any(
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398",
+))]
+impl Adc34InjectedTrigger {
+    /// Conversion to bits for JEXTSEL
+    fn bitcode(self) -> u32 {
+        match self {
+            Adc34InjectedTrigger::Tim1Trgo => 0,
+            Adc34InjectedTrigger::Tim1Cc4 => 1,
+            Adc34InjectedTrigger::Tim4Cc3 => 2,
+            Adc34InjectedTrigger::Tim8Cc2 => 3,
+            Adc34InjectedTrigger::Tim8Cc4 => 4,
+            Adc34InjectedTrigger::Tim4Cc4 => 6,
+            Adc34InjectedTrigger::Tim4Trgo => 7,
+            Adc34InjectedTrigger::Tim1Trgo2 => 8,
+            Adc34InjectedTrigger::Tim8Trgo => 9,
+            Adc34InjectedTrigger::Tim8Trgo2 => 10,
+            Adc34InjectedTrigger::Tim1Cc3 => 11,
+            Adc34InjectedTrigger::Tim3Trgo => 12,
+            Adc34InjectedTrigger::Tim2Trgo => 13,
+            Adc34InjectedTrigger::Tim7Trgo => 14,
+            Adc34InjectedTrigger::Tim15Trgo => 15,
+        }
+    }
+}
+
 /// Maps pins to ADC Channels.
 macro_rules! adc_pins {
     ($ADC:ident, $($pin:ty => $chan:expr),+ $(,)*) => {
@@ -151,9 +984,7 @@ macro_rules! adc_pins {
 }
 
 // # ADC1 Pin/Channel mapping
-// ## f303
 
-#[cfg(feature = "stm32f303")]
 adc_pins!(ADC1,
     gpioa::PA0<Analog> => 1,
     gpioa::PA1<Analog> => 2,
@@ -165,7 +996,14 @@ adc_pins!(ADC1,
     gpioc::PC3<Analog> => 9,
 );
 
-#[cfg(any(feature = "stm32f303x6", feature = "stm32f303x8"))]
+#[cfg(any(
+    feature = "stm32f301",
+    feature = "stm32f318",
+    feature = "stm32f303x6",
+    feature = "stm32f303x8",
+    feature = "stm32f334",
+    feature = "stm32f328",
+))]
 adc_pins!(ADC1,
     gpiob::PB0<Analog> => 11,
     gpiob::PB1<Analog> => 12,
@@ -173,20 +1011,37 @@ adc_pins!(ADC1,
 );
 
 #[cfg(any(
+    feature = "stm32f302",
     feature = "stm32f303xb",
     feature = "stm32f303xc",
     feature = "stm32f303xd",
     feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398",
 ))]
 adc_pins!(ADC1,
     gpiof::PF4<Analog> => 5,
     gpiof::PF2<Analog> => 10,
 );
 
+#[cfg(any(feature = "stm32f301", feature = "stm32f318",))]
+adc_pins!(ADC1,
+    gpioa::PA4<Analog> => 5,
+    gpioa::PA6<Analog> => 10,
+    gpiob::PB11<Analog> => 14,
+    gpioa::PA7<Analog> => 15,
+);
+
 // # ADC2 Pin/Channel mapping
-// ## f303
 
-#[cfg(feature = "stm32f303")]
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303",
+    feature = "stm32f334",
+    feature = "stm32f328",
+    feature = "stm32f358",
+    feature = "stm32f398",
+))]
 adc_pins!(ADC2,
     gpioa::PA4<Analog> => 1,
     gpioa::PA5<Analog> => 2,
@@ -201,7 +1056,12 @@ adc_pins!(ADC2,
     gpiob::PB2<Analog> => 12,
 );
 
-#[cfg(any(feature = "stm32f303x6", feature = "stm32f303x8"))]
+#[cfg(any(
+    feature = "stm32f303x6",
+    feature = "stm32f303x8",
+    feature = "stm32f334",
+    feature = "stm32f328",
+))]
 adc_pins!(ADC2,
     gpiob::PB12<Analog> => 13,
     gpiob::PB14<Analog> => 14,
@@ -209,23 +1069,27 @@ adc_pins!(ADC2,
 );
 
 #[cfg(any(
+    feature = "stm32f302",
     feature = "stm32f303xb",
     feature = "stm32f303xc",
     feature = "stm32f303xd",
     feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398",
 ))]
 adc_pins!(ADC2,
     gpiof::PF2<Analog> => 10,
 );
 
 // # ADC3 Pin/Channel mapping
-// ## f303
 
 #[cfg(any(
     feature = "stm32f303xb",
     feature = "stm32f303xc",
     feature = "stm32f303xd",
     feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398",
 ))]
 adc_pins!(ADC3,
     gpiob::PB1<Analog> => 1,
@@ -247,13 +1111,14 @@ adc_pins!(ADC3,
 );
 
 // # ADC4 Pin/Channel mapping
-// ## f303
 
 #[cfg(any(
     feature = "stm32f303xb",
     feature = "stm32f303xc",
     feature = "stm32f303xd",
     feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398",
 ))]
 adc_pins!(ADC4,
     gpioe::PE14<Analog> => 1,
@@ -271,12 +1136,162 @@ adc_pins!(ADC4,
     gpiod::PD9<Analog> => 13,
 );
 
+// # Internal channels
+
+pub use self::internal::to_millivolts;
+
+/// Internal temperature sensor, connected to channel 16 of ADC1
+///
+/// The sensor needs a sampling time of at least 2.2 µs.
+pub struct Temperature {
+    _0: (),
+}
+
+impl Temperature {
+    /// Enables the temperature sensor
+    pub fn enable(adc_common: &mut ADC1_2) -> Self {
+        adc_common.ccr.modify(|_, w| w.tsen().set_bit());
+        Temperature { _0: () }
+    }
+
+    /// Disables the temperature sensor
+    pub fn disable(self, adc_common: &mut ADC1_2) {
+        adc_common.ccr.modify(|_, w| w.tsen().clear_bit());
+    }
+
+    /// Converts a 12 bit right aligned sample into degrees Celsius
+    ///
+    /// This uses the factory calibration values TS_CAL1 and TS_CAL2.
+    /// `vdda` is the analog supply voltage in millivolts. Returns `None`, if
+    /// the calibration values are equal, e.g. because they are erased.
+    pub fn to_celsius(sample: u16, vdda: u32) -> Option<f32> {
+        internal::to_celsius(sample, vdda)
+    }
+}
+
+impl Channel<ADC1> for Temperature {
+    type ID = u8;
+
+    fn channel() -> u8 {
+        16
+    }
+}
+
+/// Backup supply voltage VBAT, connected to channel 17 of ADC1
+///
+/// VBAT is divided by 2 by an internal bridge, which is enabled as long as
+/// the channel is enabled and drains the battery.
+pub struct VBat {
+    _0: (),
+}
+
+impl VBat {
+    /// Enables the VBAT channel
+    pub fn enable(adc_common: &mut ADC1_2) -> Self {
+        adc_common.ccr.modify(|_, w| w.vbaten().set_bit());
+        VBat { _0: () }
+    }
+
+    /// Disables the VBAT channel
+    pub fn disable(self, adc_common: &mut ADC1_2) {
+        adc_common.ccr.modify(|_, w| w.vbaten().clear_bit());
+    }
+
+    /// Converts a 12 bit right aligned sample into the VBAT voltage in millivolts
+    ///
+    /// `vdda` is the analog supply voltage in millivolts.
+    pub fn to_millivolts(sample: u16, vdda: u32) -> u32 {
+        to_millivolts(sample, vdda) * 2
+    }
+}
+
+impl Channel<ADC1> for VBat {
+    type ID = u8;
+
+    fn channel() -> u8 {
+        17
+    }
+}
+
+/// Internal reference voltage VREFINT, connected to channel 18
+///
+/// `COMMON` is the common register block of the ADC pair, in which the
+/// reference voltage is enabled. The channel needs a sampling time of at
+/// least 2.2 µs.
+pub struct VRef<COMMON> {
+    _common: PhantomData<COMMON>,
+}
+
+impl<COMMON> VRef<COMMON> {
+    /// Returns the factory calibration value VREFINT_CAL
+    pub fn calibration() -> u16 {
+        internal::vrefint_cal()
+    }
+
+    /// Returns the analog supply voltage VDDA in millivolts
+    ///
+    /// `sample` is a 12 bit right aligned conversion of VREFINT. Returns
+    /// `None` for a sample of 0, which does not correspond to a voltage.
+    pub fn vdda(sample: u16) -> Option<u32> {
+        internal::vdda(sample)
+    }
+}
+
+macro_rules! vref {
+    ($ADC_COMMON:ident: [$($ADC:ident),+]) => {
+        impl VRef<$ADC_COMMON> {
+            /// Enables the internal reference voltage for the ADC pair
+            pub fn enable(adc_common: &mut $ADC_COMMON) -> Self {
+                adc_common.ccr.modify(|_, w| w.vrefen().set_bit());
+                VRef { _common: PhantomData }
+            }
+
+            /// Disables the internal reference voltage for the ADC pair
+            pub fn disable(self, adc_common: &mut $ADC_COMMON) {
+                adc_common.ccr.modify(|_, w| w.vrefen().clear_bit());
+            }
+        }
+
+        $(
+            impl Channel<$ADC> for VRef<$ADC_COMMON> {
+                type ID = u8;
+
+                fn channel() -> u8 {
+                    18
+                }
+            }
+        )+
+    };
+}
+
+#[cfg(any(feature = "stm32f301", feature = "stm32f318",))]
+vref!(ADC1_2: [ADC1]);
+
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303",
+    feature = "stm32f334",
+    feature = "stm32f328",
+    feature = "stm32f358",
+    feature = "stm32f398",
+))]
+vref!(ADC1_2: [ADC1, ADC2]);
+
+#[cfg(any(
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398",
+))]
+vref!(ADC3_4: [ADC3, ADC4]);
+
 // Abstract implementation of ADC functionality
 // Do not use directly. See adc12_hal for a applicable Macro.
-// TODO: Extend/generalize beyond f303
 macro_rules! adc_hal {
     ($(
-            $ADC:ident: ($adcx:ident, $ADC_COMMON:ident),
+            $ADC:ident: ($adcx:ident, $ADC_COMMON:ident, $Trigger:ident, $InjectedTrigger:ident),
     )+) => {
         $(
             impl Adc<$ADC> {
@@ -297,7 +1312,55 @@ macro_rules! adc_hal {
                     ckmode: CkMode,
                     clocks: Clocks,
                 ) -> Self {
-                    let mut this_adc = Self {
+                    let mut this_adc = Self::init(rb, adc_common, ahb, ckmode, clocks);
+                    this_adc.calibrate();
+                    // ADEN bit cannot be set during ADCAL=1
+                    // and 4 ADC clock cycle after the ADCAL
+                    // bit is cleared by hardware
+                    this_adc.wait_adc_clk_cycles(4);
+                    this_adc.enable();
+
+                    this_adc
+                }
+
+                /// Init a new ADC with a known calibration factor
+                ///
+                /// Enables the clock and the ADC like the regular init, but the
+                /// calibration is skipped. Instead the factor, which was read with
+                /// [`calibration_factor`](#method.calibration_factor) after an
+                /// earlier calibration, is restored.
+                ///
+                /// # Panics
+                /// If one of the following occurs:
+                /// * the clocksetting is not well defined.
+                /// * the clock was already enabled with a different setting
+                pub fn with_calibration_factor(
+                    rb: $ADC,
+                    adc_common : &mut $ADC_COMMON,
+                    ahb: &mut AHB,
+                    ckmode: CkMode,
+                    clocks: Clocks,
+                    factor: CalibrationFactor,
+                ) -> Self {
+                    let mut this_adc = Self::init(rb, adc_common, ahb, ckmode, clocks);
+                    if !this_adc.rb.cr.read().advregen().is_enabled() {
+                        this_adc.advregen_enable();
+                        this_adc.wait_advregen_startup();
+                    }
+                    this_adc.enable();
+                    this_adc.set_calibration_factor(factor);
+
+                    this_adc
+                }
+
+                fn init(
+                    rb: $ADC,
+                    adc_common : &mut $ADC_COMMON,
+                    ahb: &mut AHB,
+                    ckmode: CkMode,
+                    clocks: Clocks,
+                ) -> Self {
+                    let this_adc = Self {
                         rb,
                         clocks,
                         ckmode,
@@ -310,12 +1373,6 @@ macro_rules! adc_hal {
                         panic!("Clock already enabled with a different setting");
                     }
                     this_adc.set_align(Align::default());
-                    this_adc.calibrate();
-                    // ADEN bit cannot be set during ADCAL=1
-                    // and 4 ADC clock cycle after the ADCAL
-                    // bit is cleared by hardware
-                    this_adc.wait_adc_clk_cycles(4);
-                    this_adc.enable();
 
                     this_adc
                 }
@@ -332,11 +1389,13 @@ macro_rules! adc_hal {
 
                 /// sets up adc in one shot mode for a single channel
                 pub fn setup_oneshot(&mut self) {
-                    self.rb.cr.modify(|_, w| w.adstp().stop());
+                    self.set_external_trigger(None);
                     self.rb.isr.modify(|_, w| w.ovr().clear());
 
                     self.rb.cfgr.modify(|_, w| w
                         .cont().single()
+                        .discen().clear_bit()
+                        .dmaen().clear_bit()
                         .ovrmod().preserve()
                     );
 
@@ -345,6 +1404,467 @@ macro_rules! adc_hal {
                     self.operation_mode = Some(OperationMode::OneShot);
                 }
 
+                /// Sets up the adc to convert a sequence of channels
+                ///
+                /// The conversion is started with [`start_conversion`](#method.start_conversion),
+                /// [`convert_sequence`](#method.convert_sequence) or by [`read_dma`](#method.read_dma).
+                ///
+                /// # Panics
+                ///
+                /// Panics if the sequence is empty or if the number of channels of
+                /// `ConversionMode::Discontinuous` is not in 1..=8.
+                pub fn setup_sequence(&mut self, sequence: &Sequence<$ADC>, mode: ConversionMode) {
+                    assert!(!sequence.is_empty(), "ADC sequence length must be in 1..=16");
+
+                    self.stop_conversion();
+                    self.rb.isr.write(|w| w.ovr().clear());
+
+                    let mut sqr = [0u32; 4];
+                    for (position, (&chan, &smp)) in sequence.channels[..sequence.len]
+                        .iter()
+                        .zip(sequence.sample_times[..sequence.len].iter())
+                        .enumerate()
+                    {
+                        self.set_chan_smps(chan, smp);
+
+                        // SQ1..SQ4 are in SQR1 after the sequence length L,
+                        // SQ5..SQ16 are distributed over SQR2..SQR4, 5 per register.
+                        let slot = position + 1;
+                        let (index, shift) = (slot / 5, 6 * (slot % 5));
+                        sqr[index] |= u32::from(chan) << shift;
+                    }
+                    sqr[0] |= (sequence.len() - 1) as u32;
+
+                    // NOTE(unsafe): every channel is the x in ADCn_INx of a mapped pin
+                    unsafe {
+                        self.rb.sqr1.write(|w| w.bits(sqr[0]));
+                        self.rb.sqr2.write(|w| w.bits(sqr[1]));
+                        self.rb.sqr3.write(|w| w.bits(sqr[2]));
+                        self.rb.sqr4.write(|w| w.bits(sqr[3]));
+                    }
+
+                    match mode {
+                        ConversionMode::Single => self.rb.cfgr.modify(|_, w| w
+                            .cont().single()
+                            .discen().clear_bit()
+                        ),
+                        ConversionMode::Continuous => self.rb.cfgr.modify(|_, w| w
+                            .cont().continuous()
+                            .discen().clear_bit()
+                        ),
+                        ConversionMode::Discontinuous(n) => {
+                            assert!((1..=8).contains(&n), "ADC discontinuous group length must be in 1..=8");
+                            self.rb.cfgr.modify(|_, w| w
+                                .cont().single()
+                                .discen().set_bit()
+                            );
+                            // NOTE(unsafe): only DISCNUM is modified, n - 1 fits into 3 bits
+                            self.rb.cfgr.modify(|r, w| unsafe {
+                                w.bits((r.bits() & !(0b111 << 17)) | (u32::from(n - 1) << 17))
+                            });
+                        }
+                    }
+                    self.rb.cfgr.modify(|_, w| w.ovrmod().preserve());
+
+                    self.operation_mode = Some(OperationMode::Sequence(mode));
+                }
+
+                /// Selects the external event, which starts the conversion of
+                /// the regular sequence, or `None` to start it by software only
+                ///
+                /// With an external trigger, [`start_conversion`](#method.start_conversion)
+                /// does not start the conversion right away, but arms the ADC
+                /// for the next trigger event.
+                pub fn set_external_trigger(&mut self, trigger: Option<($Trigger, TriggerEdge)>) {
+                    self.stop_conversion();
+                    let bits = match trigger {
+                        Some((event, edge)) => (edge.bitcode() << 10) | (event.bitcode() << 6),
+                        None => 0,
+                    };
+                    if trigger.is_some() && self.operation_mode == Some(OperationMode::OneShot) {
+                        // One shot conversions are started by software
+                        self.operation_mode = None;
+                    }
+                    // NOTE(unsafe): only EXTEN and EXTSEL are modified
+                    self.rb.cfgr.modify(|r, w| unsafe {
+                        w.bits((r.bits() & !(0b11_1111 << 6)) | bits)
+                    });
+                }
+
+                /// Starts the conversion of the configured sequence
+                pub fn start_conversion(&mut self) {
+                    self.rb.isr.write(|w| w.eoc().clear().eos().clear());
+                    self.rb.cr.modify(|_, w| w.adstart().start());
+                }
+
+                /// Stops an ongoing conversion
+                pub fn stop_conversion(&mut self) {
+                    if self.rb.cr.read().adstart().bit_is_set() {
+                        self.rb.cr.modify(|_, w| w.adstp().stop());
+                        while self.rb.cr.read().adstart().bit_is_set() {}
+                    }
+                }
+
+                /// Returns the result of the next conversion of the sequence
+                ///
+                /// The error signals an overrun, i.e. at least one result has been
+                /// lost, because it was not read in time.
+                pub fn read_data(&mut self) -> nb::Result<u16, ()> {
+                    let isr = self.rb.isr.read();
+                    if isr.ovr().bit_is_set() {
+                        self.rb.isr.write(|w| w.ovr().clear());
+                        Err(nb::Error::Other(()))
+                    } else if isr.eoc().bit_is_set() {
+                        // Reading DR clears EOC
+                        Ok(self.rb.dr.read().rdata().bits())
+                    } else {
+                        Err(nb::Error::WouldBlock)
+                    }
+                }
+
+                /// Converts the configured sequence and writes the results into `results`
+                ///
+                /// Converts as many channels as fit into `results`. In discontinuous
+                /// mode, the next group is started automatically. In continuous mode,
+                /// the conversion is stopped afterwards.
+                pub fn convert_sequence(&mut self, results: &mut [u16]) -> Result<(), ()> {
+                    self.rb.isr.write(|w| w.ovr().clear());
+                    for result in results.iter_mut() {
+                        if self.rb.cr.read().adstart().bit_is_clear()
+                            && self.rb.isr.read().eoc().bit_is_clear()
+                        {
+                            self.start_conversion();
+                        }
+                        *result = nb::block!(self.read_data())?;
+                    }
+                    self.stop_conversion();
+                    Ok(())
+                }
+
+                /// Fill the buffer with the results of the configured sequence using DMA.
+                ///
+                /// The conversion is started right away. In `DmaMode::Circular`, the
+                /// ADC has to be in `ConversionMode::Continuous` to keep the buffer updated.
+                pub fn read_dma<B, C>(
+                    self,
+                    buffer: B,
+                    mut channel: C,
+                    mode: DmaMode,
+                ) -> dma::Transfer<B, C, Self>
+                where
+                    Self: dma::OnChannel<C>,
+                    B: dma::WriteBuffer<Word = u16> + 'static,
+                    C: dma::Channel,
+                {
+                    // NOTE(unsafe) taking the address of a register
+                    let pa = &self.rb.dr as *const _ as u32;
+                    channel.set_peripheral_address(pa, dma::Increment::Disable);
+
+                    self.rb.cfgr.modify(|_, w| w.dmacfg().bit(mode == DmaMode::Circular));
+
+                    match mode {
+                        DmaMode::OneShot => dma::Transfer::start_write(buffer, channel, self),
+                        DmaMode::Circular => dma::Transfer::start_write_circular(buffer, channel, self),
+                    }
+                }
+
+                /// Sets up the injected group of the adc
+                ///
+                /// Ongoing regular and injected conversions are stopped, as the
+                /// sample times can only be changed in between conversions.
+                /// The injected sequence is started by [`start_injected`](#method.start_injected)
+                /// with a software trigger, or armed for the external trigger otherwise.
+                ///
+                /// # Panics
+                ///
+                /// Panics if the sequence is empty.
+                pub fn setup_injected(
+                    &mut self,
+                    sequence: &InjectedSequence<$ADC>,
+                    trigger: Option<($InjectedTrigger, TriggerEdge)>,
+                ) {
+                    assert!(!sequence.is_empty(), "ADC injected sequence length must be in 1..=4");
+
+                    self.stop_injected();
+                    self.stop_conversion();
+                    self.rb.isr.write(|w| w.jqovf().clear());
+
+                    // JL, JEXTSEL and JEXTEN followed by JSQ1..JSQ4
+                    let mut jsqr = (sequence.len() - 1) as u32;
+                    if let Some((event, edge)) = trigger {
+                        jsqr |= (edge.bitcode() << 6) | (event.bitcode() << 2);
+                    }
+                    for (rank, (&chan, &smp)) in sequence.channels[..sequence.len]
+                        .iter()
+                        .zip(sequence.sample_times[..sequence.len].iter())
+                        .enumerate()
+                    {
+                        self.set_chan_smps(chan, smp);
+                        jsqr |= u32::from(chan) << (8 + 6 * rank);
+                    }
+
+                    // NOTE(unsafe): every channel is the x in ADCn_INx of a mapped pin
+                    self.rb.jsqr.write(|w| unsafe { w.bits(jsqr) });
+                }
+
+                /// Starts the conversion of the injected sequence, or arms the
+                /// adc for the external trigger of the injected sequence
+                pub fn start_injected(&mut self) {
+                    self.rb.isr.write(|w| w.jeoc().clear().jeos().clear());
+                    self.rb.cr.modify(|_, w| w.jadstart().start());
+                }
+
+                /// Stops an ongoing injected conversion
+                pub fn stop_injected(&mut self) {
+                    if self.rb.cr.read().jadstart().bit_is_set() {
+                        self.rb.cr.modify(|_, w| w.jadstp().stop());
+                        while self.rb.cr.read().jadstart().bit_is_set() {}
+                    }
+                }
+
+                /// Returns the results of the injected sequence, once it has been completed
+                ///
+                /// The results are ordered by rank. Ranks beyond the length of the
+                /// sequence hold stale values. This is meant to be called from the
+                /// interrupt handler of `Event::InjectedEndOfSequence`, it clears
+                /// the end of sequence flag. The error signals an overflow of the
+                /// injected context queue.
+                pub fn read_injected(&mut self) -> nb::Result<[u16; 4], ()> {
+                    let isr = self.rb.isr.read();
+                    if isr.jqovf().bit_is_set() {
+                        self.rb.isr.write(|w| w.jqovf().clear());
+                        Err(nb::Error::Other(()))
+                    } else if isr.jeos().bit_is_set() {
+                        self.rb.isr.write(|w| w.jeoc().clear().jeos().clear());
+                        Ok([
+                            self.injected_data(1),
+                            self.injected_data(2),
+                            self.injected_data(3),
+                            self.injected_data(4),
+                        ])
+                    } else {
+                        Err(nb::Error::WouldBlock)
+                    }
+                }
+
+                /// Returns the last result of the given rank of the injected sequence
+                ///
+                /// # Panics
+                ///
+                /// Panics if the rank is not in 1..=4.
+                pub fn injected_data(&self, rank: u8) -> u16 {
+                    let bits = match rank {
+                        1 => self.rb.jdr1.read().bits(),
+                        2 => self.rb.jdr2.read().bits(),
+                        3 => self.rb.jdr3.read().bits(),
+                        4 => self.rb.jdr4.read().bits(),
+                        _ => panic!("ADC injected rank must be in 1..=4"),
+                    };
+                    bits as u16
+                }
+
+                /// Starts listening for an interrupt event
+                pub fn listen(&mut self, event: Event) {
+                    // NOTE(unsafe): only the enable bit of the event is modified
+                    self.rb.ier.modify(|r, w| unsafe { w.bits(r.bits() | event.mask()) });
+                }
+
+                /// Stops listening for an interrupt event
+                pub fn unlisten(&mut self, event: Event) {
+                    // NOTE(unsafe): only the enable bit of the event is modified
+                    self.rb.ier.modify(|r, w| unsafe { w.bits(r.bits() & !event.mask()) });
+                }
+
+                /// Returns true, if the event has occurred
+                pub fn is_pending(&self, event: Event) -> bool {
+                    self.rb.isr.read().bits() & event.mask() != 0
+                }
+
+                /// Clears the flag of the event
+                pub fn clear_pending(&mut self, event: Event) {
+                    // NOTE(unsafe): the flags are cleared by writing 1, zeros have no effect
+                    self.rb.isr.write(|w| unsafe { w.bits(event.mask()) });
+                }
+
+                /// Configures an analog watchdog, or disables it with `None`
+                ///
+                /// The watchdog flag of [`Event`](enum.Event.html) is set, when a
+                /// guarded conversion result is outside of the thresholds.
+                ///
+                /// # Panics
+                ///
+                /// Panics if more than one channel is selected for `AnalogWatchdog::Awd1`,
+                /// which guards either one or all channels.
+                pub fn set_watchdog(&mut self, awd: AnalogWatchdog, config: Option<&Watchdog<$ADC>>) {
+                    // The configuration can only be changed in between conversions
+                    self.stop_conversion();
+                    self.stop_injected();
+
+                    match awd {
+                        AnalogWatchdog::Awd1 => {
+                            // AWD1SGL, AWD1EN, JAWD1EN and AWD1CH
+                            let cfgr = match config {
+                                Some(config) => {
+                                    let single = match config.channels {
+                                        None => 0,
+                                        Some(channels) => {
+                                            assert!(
+                                                channels.count_ones() == 1,
+                                                "the analog watchdog 1 guards one or all channels"
+                                            );
+                                            (channels.trailing_zeros() << 26) | (1 << 22)
+                                        }
+                                    };
+                                    single
+                                        | (u32::from(config.regular) << 23)
+                                        | (u32::from(config.injected) << 24)
+                                }
+                                None => 0,
+                            };
+                            // NOTE(unsafe): only the watchdog 1 fields are modified
+                            self.rb.cfgr.modify(|r, w| unsafe {
+                                w.bits((r.bits() & !((0b1_1111 << 26) | (0b111 << 22))) | cfgr)
+                            });
+                            if let Some(config) = config {
+                                // NOTE(unsafe): 12 bit thresholds
+                                self.rb.tr1.write(|w| unsafe {
+                                    w.bits(u32::from(config.high & 0xFFF) << 16 | u32::from(config.low & 0xFFF))
+                                });
+                            }
+                        }
+                        AnalogWatchdog::Awd2 | AnalogWatchdog::Awd3 => {
+                            // Guarding no channel disables the watchdog
+                            let channels = config.map_or(0, |config| config.channels.unwrap_or(0x7_FFFE));
+                            // Compared against the 8 most significant bits of the result
+                            let tr = config.map_or(0, |config| {
+                                u32::from(config.high >> 4 & 0xFF) << 16 | u32::from(config.low >> 4 & 0xFF)
+                            });
+                            // NOTE(unsafe): channel bit masks and 8 bit thresholds
+                            unsafe {
+                                if awd == AnalogWatchdog::Awd2 {
+                                    self.rb.tr2.write(|w| w.bits(tr));
+                                    self.rb.awd2cr.write(|w| w.bits(channels));
+                                } else {
+                                    self.rb.tr3.write(|w| w.bits(tr));
+                                    self.rb.awd3cr.write(|w| w.bits(channels));
+                                }
+                            }
+                        }
+                    }
+                }
+
+                /// Subtracts `value` from every result of the channel
+                ///
+                /// The results of the channel are signed then, i.e. negative
+                /// values are sign extended to 16 bits and can be interpreted
+                /// as `i16`.
+                ///
+                /// # Panics
+                ///
+                /// Panics if `offset` is not in 1..=4, there are four offset registers.
+                pub fn set_offset<PIN>(&mut self, offset: u8, _pin: &PIN, value: u16)
+                where
+                    PIN: Channel<$ADC, ID = u8>,
+                {
+                    // OFFSETy_EN, OFFSETy_CH and OFFSETy
+                    let bits = (1 << 31) | (u32::from(PIN::channel()) << 26) | u32::from(value & 0xFFF);
+                    self.write_offset(offset, bits);
+                }
+
+                /// Disables an offset register
+                ///
+                /// # Panics
+                ///
+                /// Panics if `offset` is not in 1..=4, there are four offset registers.
+                pub fn disable_offset(&mut self, offset: u8) {
+                    self.write_offset(offset, 0);
+                }
+
+                fn write_offset(&mut self, offset: u8, bits: u32) {
+                    // The offsets can only be changed in between conversions
+                    self.stop_conversion();
+                    self.stop_injected();
+
+                    // NOTE(unsafe): the channel is the x in ADCn_INx of a mapped pin
+                    unsafe {
+                        match offset {
+                            1 => self.rb.ofr1.write(|w| w.bits(bits)),
+                            2 => self.rb.ofr2.write(|w| w.bits(bits)),
+                            3 => self.rb.ofr3.write(|w| w.bits(bits)),
+                            4 => self.rb.ofr4.write(|w| w.bits(bits)),
+                            _ => panic!("ADC offset register must be in 1..=4"),
+                        }
+                    }
+                }
+
+                /// Converts the channel of `positive` in differential mode
+                ///
+                /// The negative input of a differential channel is the next
+                /// channel, so both pins have to be in analog mode. The ADC is
+                /// disabled shortly, running conversions are stopped.
+                ///
+                /// # Panics
+                ///
+                /// Panics if the channel of `negative` does not follow the one of `positive`.
+                pub fn set_differential<P, N>(&mut self, _positive: &P, _negative: &N)
+                where
+                    P: Channel<$ADC, ID = u8>,
+                    N: Channel<$ADC, ID = u8>,
+                {
+                    assert!(
+                        N::channel() == P::channel() + 1,
+                        "the negative input must be the channel following the positive input"
+                    );
+                    self.modify_difsel(P::channel(), true);
+                }
+
+                /// Converts the channel in single ended mode, which is the default
+                ///
+                /// The ADC is disabled shortly, running conversions are stopped.
+                pub fn set_single_ended<PIN>(&mut self, _pin: &PIN)
+                where
+                    PIN: Channel<$ADC, ID = u8>,
+                {
+                    self.modify_difsel(PIN::channel(), false);
+                }
+
+                fn modify_difsel(&mut self, chan: u8, differential: bool) {
+                    // DIFSEL can only be written, while the ADC is disabled
+                    self.disable();
+                    // NOTE(unsafe): only the bit of the channel is modified
+                    self.rb.difsel.modify(|r, w| unsafe {
+                        w.bits(if differential {
+                            r.bits() | 1 << chan
+                        } else {
+                            r.bits() & !(1 << chan)
+                        })
+                    });
+                    self.enable();
+                }
+
+                /// Returns the calibration factors determined at initialization
+                ///
+                /// They can be stored to init the ADC later on with
+                /// [`with_calibration_factor`](#method.with_calibration_factor).
+                pub fn calibration_factor(&self) -> CalibrationFactor {
+                    let calfact = self.rb.calfact.read().bits();
+                    CalibrationFactor {
+                        single_ended: (calfact & 0x7F) as u8,
+                        differential: (calfact >> 16 & 0x7F) as u8,
+                    }
+                }
+
+                /// Restores previously read calibration factors
+                pub fn set_calibration_factor(&mut self, factor: CalibrationFactor) {
+                    // CALFACT can only be written in between conversions
+                    self.stop_conversion();
+                    self.stop_injected();
+                    // NOTE(unsafe): 7 bit calibration factors
+                    self.rb.calfact.write(|w| unsafe {
+                        w.bits(u32::from(factor.differential & 0x7F) << 16 | u32::from(factor.single_ended & 0x7F))
+                    });
+                }
+
                 fn set_sequence_len(&mut self, len: u8) {
                     assert!(len - 1 < 16, "ADC sequence length must be in 1..=16");
                     self.rb.sqr1.modify(|_, w| w.l().bits(len - 1));
@@ -355,15 +1875,26 @@ macro_rules! adc_hal {
                 }
 
                 fn enable(&mut self) {
+                    // ADRDY may still be set from a previous enable
+                    self.rb.isr.write(|w| w.adrdy().clear());
                     self.rb.cr.modify(|_, w| w.aden().enable());
                     while self.rb.isr.read().adrdy().is_not_ready() {}
                 }
 
                 fn disable(&mut self) {
-                    self.rb.cr.modify(|_, w| w.addis().disable());
+                    // ADDIS must not be set, while the ADC is disabled
+                    if self.rb.cr.read().aden().bit_is_set() {
+                        self.stop_conversion();
+                        self.stop_injected();
+                        self.rb.cr.modify(|_, w| w.addis().disable());
+                        while self.rb.cr.read().aden().bit_is_set() {}
+                    }
                 }
 
                 /// Calibrate according to 15.3.8 in the Reference Manual
+                ///
+                /// Both, the single ended and the differential calibration
+                /// factor, are determined.
                 fn calibrate(&mut self) {
                     if !self.rb.cr.read().advregen().is_enabled() {
                         self.advregen_enable();
@@ -377,6 +1908,15 @@ macro_rules! adc_hal {
                         .adcal()   .calibration());
 
                     while self.rb.cr.read().adcal().is_calibration() {}
+
+                    // ADCAL must not be set again right after it was cleared
+                    self.wait_adc_clk_cycles(4);
+
+                    self.rb.cr.modify(|_, w| w
+                        .adcaldif().differential()
+                        .adcal()   .calibration());
+
+                    while self.rb.cr.read().adcal().is_calibration() {}
                 }
 
                 fn wait_adc_clk_cycles(&self, cycles: u32) {
@@ -400,7 +1940,9 @@ macro_rules! adc_hal {
                 /// busy ADC read
                 fn convert_one(&mut self, chan: u8) -> u16 {
                     self.ensure_oneshot();
-                    self.set_chan_smps(chan, SampleTime::default());
+                    // The internal channels need a sampling time of at least 2.2 µs
+                    let smp = if chan >= 16 { SampleTime::T_181 } else { SampleTime::default() };
+                    self.set_chan_smps(chan, smp);
                     self.select_single_chan(chan);
 
                     self.rb.cr.modify(|_, w| w.adstart().start());
@@ -437,7 +1979,8 @@ macro_rules! adc_hal {
                         7 => self.rb.smpr1.modify(|_, w| w.smp7().bits(smp.bitcode())),
                         8 => self.rb.smpr1.modify(|_, w| w.smp8().bits(smp.bitcode())),
                         9 => self.rb.smpr1.modify(|_, w| w.smp9().bits(smp.bitcode())),
-                        11 => self.rb.smpr2.modify(|_, w| w.smp10().bits(smp.bitcode())),
+                        10 => self.rb.smpr2.modify(|_, w| w.smp10().bits(smp.bitcode())),
+                        11 => self.rb.smpr2.modify(|_, w| w.smp11().bits(smp.bitcode())),
                         12 => self.rb.smpr2.modify(|_, w| w.smp12().bits(smp.bitcode())),
                         13 => self.rb.smpr2.modify(|_, w| w.smp13().bits(smp.bitcode())),
                         14 => self.rb.smpr2.modify(|_, w| w.smp14().bits(smp.bitcode())),
@@ -451,6 +1994,20 @@ macro_rules! adc_hal {
 
             }
 
+            impl dma::Target for Adc<$ADC> {
+                fn enable_dma(&mut self) {
+                    self.rb.cfgr.modify(|_, w| w.dmaen().set_bit());
+                    // A pending DMA request is held until the data register is
+                    // read, so the conversion can start before the channel is enabled.
+                    self.start_conversion();
+                }
+
+                fn disable_dma(&mut self) {
+                    self.stop_conversion();
+                    self.rb.cfgr.modify(|_, w| w.dmaen().clear_bit());
+                }
+            }
+
             impl<WORD, PIN> OneShot<$ADC, WORD, PIN> for Adc<$ADC>
             where
                 WORD: From<u16>,
@@ -468,10 +2025,9 @@ macro_rules! adc_hal {
 }
 
 // Macro to implement ADC functionallity for ADC1 and ADC2
-// TODO: Extend/differentiate beyond f303.
 macro_rules! adc12_hal {
     ($(
-            $ADC:ident: ($adcx:ident),
+            $ADC:ident: ($adcx:ident, $adcxen:ident),
     )+) => {
         $(
             impl Adc<$ADC> {
@@ -479,10 +2035,10 @@ macro_rules! adc12_hal {
                 ///     the clock can be enabled with the given settings
                 ///  or the clock was already enabled with the same settings
                 fn enable_clock(&self, ahb: &mut AHB, adc_common: &mut ADC1_2) -> bool {
-                    if ahb.enr().read().adc12en().is_enabled() {
+                    if ahb.enr().read().$adcxen().is_enabled() {
                         return (adc_common.ccr.read().ckmode().variant() == self.ckmode.into());
                     }
-                    ahb.enr().modify(|_, w| w.adc12en().enabled());
+                    ahb.enr().modify(|_, w| w.$adcxen().enabled());
                     adc_common.ccr.modify(|_, w| w
                         .ckmode().variant(self.ckmode.into())
                     );
@@ -490,19 +2046,20 @@ macro_rules! adc12_hal {
                 }
             }
             adc_hal! {
-                $ADC: ($adcx, ADC1_2),
+                $ADC: ($adcx, ADC1_2, Adc12Trigger, Adc12InjectedTrigger),
             }
         )+
     }
 }
 
 // Macro to implement ADC functionallity for ADC3 and ADC4
-// TODO: Extend/differentiate beyond f303.
 #[cfg(any(
     feature = "stm32f303xb",
     feature = "stm32f303xc",
     feature = "stm32f303xd",
     feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398",
 ))]
 macro_rules! adc34_hal {
     ($(
@@ -525,22 +2082,35 @@ macro_rules! adc34_hal {
                 }
             }
             adc_hal! {
-                $ADC: ($adcx, ADC3_4),
+                $ADC: ($adcx, ADC3_4, Adc34Trigger, Adc34InjectedTrigger),
             }
         )+
     }
 }
 
-#[cfg(feature = "stm32f303")]
+#[cfg(any(feature = "stm32f301", feature = "stm32f318",))]
+adc12_hal! {
+    ADC1: (adc1, adc1en),
+}
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303",
+    feature = "stm32f334",
+    feature = "stm32f328",
+    feature = "stm32f358",
+    feature = "stm32f398",
+))]
 adc12_hal! {
-    ADC1: (adc1),
-    ADC2: (adc2),
+    ADC1: (adc1, adc12en),
+    ADC2: (adc2, adc12en),
 }
 #[cfg(any(
     feature = "stm32f303xb",
     feature = "stm32f303xc",
     feature = "stm32f303xd",
     feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398",
 ))]
 adc34_hal! {
     ADC3: (adc3),
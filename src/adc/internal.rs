@@ -0,0 +1,50 @@
+//! Conversions of the internal channels, which are based on the factory
+//! calibration values and shared by both ADC implementations
+
+use core::ptr;
+
+/// Address of the temperature sensor reading at 30 °C and VDDA = 3.3 V
+const TS_CAL1: *const u16 = 0x1FFF_F7B8 as *const u16;
+/// Address of the temperature sensor reading at 110 °C and VDDA = 3.3 V
+const TS_CAL2: *const u16 = 0x1FFF_F7C2 as *const u16;
+/// Address of the VREFINT reading at 30 °C and VDDA = 3.3 V
+const VREFINT_CAL: *const u16 = 0x1FFF_F7BA as *const u16;
+/// VDDA in millivolts, at which the factory calibration values were taken
+const VDDA_CALIB_MV: u32 = 3300;
+
+/// Converts a 12 bit right aligned sample into millivolts
+///
+/// `vdda` is the analog supply voltage in millivolts, which can be
+/// determined with [`VRef::vdda`](struct.VRef.html#method.vdda).
+pub fn to_millivolts(sample: u16, vdda: u32) -> u32 {
+    u32::from(sample) * vdda / 4095
+}
+
+/// Returns the factory calibration value VREFINT_CAL
+pub(super) fn vrefint_cal() -> u16 {
+    // NOTE(unsafe) read of factory programmed, read only memory
+    unsafe { ptr::read(VREFINT_CAL) }
+}
+
+/// Returns VDDA in millivolts for a conversion of VREFINT
+pub(super) fn vdda(sample: u16) -> Option<u32> {
+    if sample == 0 {
+        return None;
+    }
+    Some(VDDA_CALIB_MV * u32::from(vrefint_cal()) / u32::from(sample))
+}
+
+/// Converts a conversion of the temperature sensor into degrees Celsius
+pub(super) fn to_celsius(sample: u16, vdda: u32) -> Option<f32> {
+    // NOTE(unsafe) read of factory programmed, read only memory
+    let (cal1, cal2) = unsafe { (ptr::read(TS_CAL1), ptr::read(TS_CAL2)) };
+    if cal1 == cal2 {
+        return None;
+    }
+    // Scale the sample to the supply voltage of the calibration
+    let sample = (u32::from(sample) * vdda) as f32 / VDDA_CALIB_MV as f32;
+    Some(
+        (110.0 - 30.0) * (sample - f32::from(cal1)) / (i32::from(cal2) - i32::from(cal1)) as f32
+            + 30.0,
+    )
+}
@@ -0,0 +1,341 @@
+//! API for the ADC (Analog to Digital Converter) of the STM32F373 and STM32F378
+//!
+//! Those devices have a single 12 bit SAR ADC of an older generation, which
+//! differs from the ADCs of the other STM32F3 devices. The 16 bit sigma-delta
+//! ADCs are not covered by this module.
+//!
+//! ```
+//! let mut adc1 = adc::Adc::adc1(dp.ADC1, &mut rcc.apb2, clocks);
+//! let mut pa0 = gpioa.pa0.into_analog(&mut gpioa.moder, &mut gpioa.pupdr);
+//! let data: u16 = adc1.read(&mut pa0).unwrap();
+//! ```
+use cortex_m::asm;
+use embedded_hal::adc::{Channel, OneShot};
+
+use crate::{
+    gpio::{gpioa, gpiob, gpioc, Analog},
+    pac::{ADC1, RCC},
+    rcc::{Clocks, APB2},
+};
+
+#[path = "adc/internal.rs"]
+mod internal;
+
+/// Maximum frequency of the ADC clock
+const MAX_ADC_CLK_HZ: u32 = 14_000_000;
+
+/// ADC configuration
+pub struct Adc<ADC> {
+    rb: ADC,
+    clocks: Clocks,
+    /// Frequency of the ADC clock in Hz
+    adc_clk: u32,
+    sample_time: SampleTime,
+}
+
+/// ADC sampling time
+///
+/// The sample time is used for all channels of the following one shot
+/// conversions, see [`Adc::set_sample_time`](struct.Adc.html#method.set_sample_time).
+/// There is always an overhead of 12.5 ADC clock cycles.
+/// E.g. For Sampletime T_28 (28.5 cycles) the total conversion time is
+/// 28.5 + 12.5 = 41 ADC Clock Cycles
+#[derive(Clone, Copy)]
+pub enum SampleTime {
+    T_1,
+    T_7,
+    T_13,
+    T_28,
+    T_41,
+    T_55,
+    T_71,
+    T_239,
+}
+
+impl Default for SampleTime {
+    /// T_1 is also the reset value.
+    fn default() -> Self {
+        SampleTime::T_1
+    }
+}
+
+impl SampleTime {
+    /// Conversion to bits for SMP
+    fn bitcode(self) -> u32 {
+        match self {
+            SampleTime::T_1 => 0b000,
+            SampleTime::T_7 => 0b001,
+            SampleTime::T_13 => 0b010,
+            SampleTime::T_28 => 0b011,
+            SampleTime::T_41 => 0b100,
+            SampleTime::T_55 => 0b101,
+            SampleTime::T_71 => 0b110,
+            SampleTime::T_239 => 0b111,
+        }
+    }
+}
+
+/// ADC data register alignment
+pub enum Align {
+    /// Right alignment of output data
+    Right,
+    /// Left alignment of output data
+    Left,
+}
+
+impl Default for Align {
+    fn default() -> Self {
+        Align::Right
+    }
+}
+
+/// Maps pins to ADC Channels.
+macro_rules! adc_pins {
+    ($ADC:ident, $($pin:ty => $chan:expr),+ $(,)*) => {
+        $(
+            impl Channel<$ADC> for $pin {
+                type ID = u8;
+
+                fn channel() -> u8 { $chan }
+            }
+        )+
+    };
+}
+
+// # ADC1 Pin/Channel mapping
+
+adc_pins!(ADC1,
+    gpioa::PA0<Analog> => 0,
+    gpioa::PA1<Analog> => 1,
+    gpioa::PA2<Analog> => 2,
+    gpioa::PA3<Analog> => 3,
+    gpioa::PA4<Analog> => 4,
+    gpioa::PA5<Analog> => 5,
+    gpioa::PA6<Analog> => 6,
+    gpioa::PA7<Analog> => 7,
+    gpiob::PB0<Analog> => 8,
+    gpiob::PB1<Analog> => 9,
+    gpioc::PC0<Analog> => 10,
+    gpioc::PC1<Analog> => 11,
+    gpioc::PC2<Analog> => 12,
+    gpioc::PC3<Analog> => 13,
+    gpioc::PC4<Analog> => 14,
+    gpioc::PC5<Analog> => 15,
+);
+
+// # Internal channels
+
+pub use self::internal::to_millivolts;
+
+/// Internal temperature sensor, connected to channel 16
+///
+/// The sensor is enabled together with `VRef` by
+/// [`Adc::enable_internal_channels`](struct.Adc.html#method.enable_internal_channels).
+pub struct Temperature {
+    _0: (),
+}
+
+impl Temperature {
+    /// Converts a 12 bit right aligned sample into degrees Celsius
+    ///
+    /// This uses the factory calibration values TS_CAL1 and TS_CAL2.
+    /// `vdda` is the analog supply voltage in millivolts. Returns `None`, if
+    /// the calibration values are equal, e.g. because they are erased.
+    pub fn to_celsius(sample: u16, vdda: u32) -> Option<f32> {
+        internal::to_celsius(sample, vdda)
+    }
+}
+
+impl Channel<ADC1> for Temperature {
+    type ID = u8;
+
+    fn channel() -> u8 {
+        16
+    }
+}
+
+/// Internal reference voltage VREFINT, connected to channel 17
+pub struct VRef {
+    _0: (),
+}
+
+impl VRef {
+    /// Returns the factory calibration value VREFINT_CAL
+    pub fn calibration() -> u16 {
+        internal::vrefint_cal()
+    }
+
+    /// Returns the analog supply voltage VDDA in millivolts
+    ///
+    /// `sample` is a 12 bit right aligned conversion of VREFINT. Returns
+    /// `None` for a sample of 0, which does not correspond to a voltage.
+    pub fn vdda(sample: u16) -> Option<u32> {
+        internal::vdda(sample)
+    }
+}
+
+impl Channel<ADC1> for VRef {
+    type ID = u8;
+
+    fn channel() -> u8 {
+        17
+    }
+}
+
+impl Adc<ADC1> {
+    /// Init a new ADC
+    ///
+    /// Enables the clock, performs a calibration and enables the ADC. The
+    /// ADC clock is derived from PCLK2 with the smallest prescaler, which
+    /// keeps it below 14 MHz.
+    ///
+    /// # Panics
+    /// If PCLK2 is too fast for the ADC clock to be below 14 MHz.
+    pub fn adc1(rb: ADC1, apb2: &mut APB2, clocks: Clocks) -> Self {
+        let pclk2 = clocks.pclk2().0;
+        let (adcpre, div) = match (2..=8)
+            .step_by(2)
+            .position(|div| pclk2 / div <= MAX_ADC_CLK_HZ)
+        {
+            Some(position) => (position as u32, 2 * (position as u32 + 1)),
+            None => panic!("PCLK2 is too fast for the ADC clock"),
+        };
+        // NOTE(unsafe) ADCPRE is only used by the ADC, which is owned by this struct
+        unsafe {
+            (*RCC::ptr())
+                .cfgr
+                .modify(|r, w| w.bits((r.bits() & !(0b11 << 14)) | (adcpre << 14)));
+        }
+
+        apb2.enr().modify(|_, w| w.adc1en().set_bit());
+        apb2.rstr().modify(|_, w| w.adc1rst().set_bit());
+        apb2.rstr().modify(|_, w| w.adc1rst().clear_bit());
+
+        let mut adc = Adc {
+            rb,
+            clocks,
+            adc_clk: pclk2 / div,
+            sample_time: SampleTime::default(),
+        };
+
+        // The conversions are started by software, by selecting SWSTART
+        // as external trigger in EXTSEL and enabling the external trigger
+        adc.modify_cr2(0b1111 << 17, (0b1 << 20) | (0b111 << 17));
+        adc.set_align(Align::default());
+        adc.enable();
+        adc.calibrate();
+
+        adc
+    }
+
+    /// Sets the sample time of the following one shot conversions
+    pub fn set_sample_time(&mut self, sample_time: SampleTime) {
+        self.sample_time = sample_time;
+    }
+
+    /// Sets the alignment of the conversion results
+    pub fn set_align(&mut self, align: Align) {
+        let bits = match align {
+            Align::Right => 0,
+            Align::Left => 0b1 << 11,
+        };
+        self.modify_cr2(0b1 << 11, bits);
+    }
+
+    /// Enables the temperature sensor and VREFINT channels
+    ///
+    /// Both need a sampling time of at least 2.2 µs.
+    pub fn enable_internal_channels(&mut self) -> (Temperature, VRef) {
+        // TSVREFE
+        self.modify_cr2(0b1 << 23, 0b1 << 23);
+        (Temperature { _0: () }, VRef { _0: () })
+    }
+
+    /// Disables the temperature sensor and VREFINT channels
+    pub fn disable_internal_channels(&mut self, _channels: (Temperature, VRef)) {
+        self.modify_cr2(0b1 << 23, 0);
+    }
+
+    /// Releases the ADC peripheral
+    ///
+    /// The ADC is powered down beforehand.
+    pub fn free(mut self) -> ADC1 {
+        // ADON
+        self.modify_cr2(0b1, 0);
+        self.rb
+    }
+
+    fn enable(&mut self) {
+        // ADON, the ADC needs the stabilization time t_STAB afterwards
+        self.modify_cr2(0b1, 0b1);
+        self.wait_adc_clk_cycles(16);
+    }
+
+    /// Calibrate according to 13.4.8 in the Reference Manual
+    fn calibrate(&mut self) {
+        // RSTCAL
+        self.modify_cr2(0b1 << 3, 0b1 << 3);
+        while self.rb.cr2.read().bits() & (0b1 << 3) != 0 {}
+        // CAL
+        self.modify_cr2(0b1 << 2, 0b1 << 2);
+        while self.rb.cr2.read().bits() & (0b1 << 2) != 0 {}
+    }
+
+    fn wait_adc_clk_cycles(&self, cycles: u32) {
+        let adc_clk_cycle = self.clocks.sysclk().0 / self.adc_clk;
+        asm::delay(adc_clk_cycle * cycles);
+    }
+
+    /// busy ADC read
+    fn convert_one(&mut self, chan: u8) -> u16 {
+        // NOTE(unsafe) chan is the x in ADC_INx, a sequence of length 1
+        unsafe {
+            self.rb.sqr1.write(|w| w.bits(0));
+            self.rb.sqr3.write(|w| w.bits(u32::from(chan)));
+        }
+        self.set_chan_smp(chan, self.sample_time);
+
+        // SWSTART
+        self.modify_cr2(0b1 << 22, 0b1 << 22);
+        // EOC, cleared by reading DR
+        while self.rb.sr.read().bits() & (0b1 << 1) == 0 {}
+        self.rb.dr.read().bits() as u16
+    }
+
+    fn set_chan_smp(&mut self, chan: u8, smp: SampleTime) {
+        let chan = u32::from(chan);
+        // NOTE(unsafe) only the SMP field of the channel is modified
+        unsafe {
+            if chan < 10 {
+                self.rb.smpr2.modify(|r, w| {
+                    w.bits((r.bits() & !(0b111 << (3 * chan))) | (smp.bitcode() << (3 * chan)))
+                });
+            } else {
+                let shift = 3 * (chan - 10);
+                self.rb.smpr1.modify(|r, w| {
+                    w.bits((r.bits() & !(0b111 << shift)) | (smp.bitcode() << shift))
+                });
+            }
+        }
+    }
+
+    fn modify_cr2(&mut self, mask: u32, bits: u32) {
+        // NOTE(unsafe) CR2 is only used by this struct; RSTCAL, CAL and
+        // SWSTART are always written as 0, unless they are in the mask
+        self.rb
+            .cr2
+            .modify(|r, w| unsafe { w.bits((r.bits() & !(mask | 0b1100 | 0b1 << 22)) | bits) });
+    }
+}
+
+impl<WORD, PIN> OneShot<ADC1, WORD, PIN> for Adc<ADC1>
+where
+    WORD: From<u16>,
+    PIN: Channel<ADC1, ID = u8>,
+{
+    type Error = ();
+
+    fn read(&mut self, _pin: &mut PIN) -> nb::Result<WORD, Self::Error> {
+        Ok(self.convert_one(PIN::channel()).into())
+    }
+}
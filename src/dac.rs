@@ -0,0 +1,333 @@
+//! Digital to Analog Converter (DAC)
+//!
+//! Each DAC channel is bound to its output pin, which has to be in analog mode.
+//!
+//! ```
+//! let mut gpioa = dp.GPIOA.split(&mut rcc.ahb);
+//! let pa4 = gpioa.pa4.into_analog(&mut gpioa.moder, &mut gpioa.pupdr);
+//!
+//! let mut dac = dac::dac1(dp.DAC1, pa4, &mut rcc.apb1);
+//! dac.enable();
+//! dac.write(2048, Format::Bits12Right);
+//! ```
+
+use core::marker::PhantomData;
+
+use cortex_m::interrupt;
+
+#[cfg(any(
+    feature = "stm32f303x6",
+    feature = "stm32f303x8",
+    feature = "stm32f334",
+    feature = "stm32f373",
+    feature = "stm32f378"
+))]
+use crate::gpio::gpioa::PA6;
+use crate::gpio::gpioa::{PA4, PA5};
+use crate::gpio::Analog;
+use crate::pac::DAC1;
+#[cfg(any(
+    feature = "stm32f303x6",
+    feature = "stm32f303x8",
+    feature = "stm32f334",
+    feature = "stm32f373",
+    feature = "stm32f378"
+))]
+use crate::pac::DAC2;
+use crate::rcc::APB1;
+
+use crate::dma;
+
+/// Data format of the values written to a DAC channel
+#[derive(Clone, Copy, PartialEq)]
+pub enum Format {
+    /// 8 bit, right aligned
+    Bits8,
+    /// 12 bit, right aligned
+    Bits12Right,
+    /// 12 bit, left aligned
+    Bits12Left,
+}
+
+/// Event, which starts a conversion
+///
+/// Only the triggers, which are available on every device, are listed.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Trigger {
+    /// Timer 6 TRGO event
+    Tim6,
+    /// Timer 7 TRGO event
+    Tim7,
+    /// Timer 2 TRGO event
+    Tim2,
+    /// EXTI line 9
+    Exti9,
+    /// Software trigger, see [`Dac::trigger`](struct.Dac.html#method.trigger)
+    Software,
+}
+
+impl Trigger {
+    /// Conversion to bits for TSEL
+    fn bitcode(self) -> u32 {
+        match self {
+            Trigger::Tim6 => 0b000,
+            Trigger::Tim7 => 0b010,
+            Trigger::Tim2 => 0b100,
+            Trigger::Exti9 => 0b110,
+            Trigger::Software => 0b111,
+        }
+    }
+}
+
+/// Built-in waveform generator
+///
+/// The generated wave is added to the value of the data register on every
+/// trigger event, so a trigger has to be selected.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Waveform {
+    /// No wave generation
+    Disabled,
+    /// Pseudo noise of the LFSR, with the given number of unmasked bits (1..=12)
+    Noise(u8),
+    /// Triangle with an amplitude of `2^n - 1`, where `n` is in 1..=12
+    Triangle(u8),
+}
+
+/// Pins, which can be used as DAC outputs
+pub trait Pins<DAC> {
+    /// The DAC channel(s) bound to the pin(s)
+    type Channels;
+
+    #[doc(hidden)]
+    fn bind(self) -> Self::Channels;
+}
+
+/// A DAC channel bound to its output pin
+pub struct Dac<DAC, PIN> {
+    pin: PIN,
+    _dac: PhantomData<DAC>,
+}
+
+macro_rules! dac_channel {
+    ($DACX:ident, $PIN:ty, $shift:expr, $swtrigx:expr, $dhr8rx:ident, $dhr12rx:ident, $dhr12lx:ident, $dorx:ident) => {
+        impl Pins<$DACX> for $PIN {
+            type Channels = Dac<$DACX, $PIN>;
+
+            fn bind(self) -> Self::Channels {
+                Dac {
+                    pin: self,
+                    _dac: PhantomData,
+                }
+            }
+        }
+
+        impl Dac<$DACX, $PIN> {
+            /// Enables the channel
+            pub fn enable(&mut self) {
+                self.modify_cr(0b1, 0b1);
+            }
+
+            /// Disables the channel
+            pub fn disable(&mut self) {
+                self.modify_cr(0b1, 0b0);
+            }
+
+            /// Enables or disables the output buffer
+            ///
+            /// The buffer reduces the output impedance, so that external loads
+            /// can be driven without an operational amplifier.
+            pub fn set_output_buffer(&mut self, enable: bool) {
+                // BOFF: 1 = buffer disabled
+                self.modify_cr(0b1 << 1, if enable { 0 } else { 0b1 << 1 });
+            }
+
+            /// Selects the event, which starts a conversion, or `None` to
+            /// convert as soon as a value is written
+            pub fn set_trigger(&mut self, trigger: Option<Trigger>) {
+                // TEN and TSEL
+                let bits = match trigger {
+                    Some(trigger) => (trigger.bitcode() << 3) | (0b1 << 2),
+                    None => 0,
+                };
+                self.modify_cr(0b1111 << 2, bits);
+            }
+
+            /// Starts a conversion, if `Trigger::Software` is selected
+            pub fn trigger(&mut self) {
+                // NOTE(unsafe) the bits of SWTRIGR are cleared by hardware,
+                // writing zeros to the other channel has no effect
+                unsafe { (*$DACX::ptr()).swtrigr.write(|w| w.bits($swtrigx)) };
+            }
+
+            /// Configures the built-in waveform generator
+            ///
+            /// # Panics
+            ///
+            /// Panics if the number of bits of a waveform is not in 1..=12.
+            pub fn set_waveform(&mut self, waveform: Waveform) {
+                // WAVE and MAMP
+                let bits = match waveform {
+                    Waveform::Disabled => 0,
+                    Waveform::Noise(n) => {
+                        assert!((1..=12).contains(&n), "number of bits must be in 1..=12");
+                        (u32::from(n - 1) << 8) | (0b01 << 6)
+                    }
+                    Waveform::Triangle(n) => {
+                        assert!((1..=12).contains(&n), "number of bits must be in 1..=12");
+                        (u32::from(n - 1) << 8) | (0b10 << 6)
+                    }
+                };
+                self.modify_cr(0b11_1111 << 6, bits);
+            }
+
+            /// Writes a value into the data holding register of the channel
+            ///
+            /// Values, which do not fit into the format, are truncated.
+            pub fn write(&mut self, value: u16, format: Format) {
+                let dac = unsafe { &*$DACX::ptr() };
+                // NOTE(unsafe) atomic write to a register only used by this channel
+                unsafe {
+                    match format {
+                        Format::Bits8 => dac.$dhr8rx.write(|w| w.bits(u32::from(value & 0xFF))),
+                        Format::Bits12Right => {
+                            dac.$dhr12rx.write(|w| w.bits(u32::from(value & 0xFFF)))
+                        }
+                        Format::Bits12Left => {
+                            dac.$dhr12lx.write(|w| w.bits(u32::from(value & 0xFFF0)))
+                        }
+                    }
+                }
+            }
+
+            /// Returns the 12 bit value, which is currently converted
+            pub fn output(&self) -> u16 {
+                // NOTE(unsafe) atomic read with no side effects
+                unsafe { (*$DACX::ptr()).$dorx.read().bits() as u16 }
+            }
+
+            /// Releases the output pin
+            ///
+            /// The channel is disabled beforehand.
+            pub fn free(mut self) -> $PIN {
+                self.disable();
+                self.pin
+            }
+
+            fn modify_cr(&mut self, mask: u32, bits: u32) {
+                // NOTE(unsafe) critical section prevents races with the other
+                // channel of this DAC
+                interrupt::free(|_| unsafe {
+                    (*$DACX::ptr())
+                        .cr
+                        .modify(|r, w| w.bits((r.bits() & !(mask << $shift)) | (bits << $shift)))
+                });
+            }
+        }
+
+        impl Dac<$DACX, $PIN> {
+            /// Stream all values of the buffer to the channel using DMA.
+            ///
+            /// One value is transferred on every trigger event, so a trigger
+            /// has to be selected with [`set_trigger`](#method.set_trigger).
+            ///
+            /// # Panics
+            ///
+            /// Panics if the word size of the buffer does not match the format.
+            /// `Format::Bits8` requires `u8` words, the others require `u16` words.
+            pub fn write_all<B, C>(
+                self,
+                buffer: B,
+                format: Format,
+                mut channel: C,
+            ) -> dma::Transfer<B, C, Self>
+            where
+                Self: dma::OnChannel<C>,
+                B: dma::ReadBuffer + 'static,
+                C: dma::Channel,
+            {
+                let word_size = core::mem::size_of::<B::Word>();
+                let dac = unsafe { &*$DACX::ptr() };
+                // NOTE(unsafe) taking the address of a register
+                let pa = match format {
+                    Format::Bits8 => {
+                        assert!(word_size == 1, "8 bit format requires u8 words");
+                        &dac.$dhr8rx as *const _ as u32
+                    }
+                    Format::Bits12Right => {
+                        assert!(word_size == 2, "12 bit formats require u16 words");
+                        &dac.$dhr12rx as *const _ as u32
+                    }
+                    Format::Bits12Left => {
+                        assert!(word_size == 2, "12 bit formats require u16 words");
+                        &dac.$dhr12lx as *const _ as u32
+                    }
+                };
+                channel.set_peripheral_address(pa, dma::Increment::Disable);
+
+                dma::Transfer::start_read(buffer, channel, self)
+            }
+        }
+
+        impl dma::Target for Dac<$DACX, $PIN> {
+            fn enable_dma(&mut self) {
+                // DMAEN
+                self.modify_cr(0b1 << 12, 0b1 << 12);
+            }
+
+            fn disable_dma(&mut self) {
+                // DMAEN
+                self.modify_cr(0b1 << 12, 0);
+            }
+        }
+    };
+}
+
+macro_rules! dac {
+    ($DACX:ident: ($dacx:ident, $dacXen:ident, $dacXrst:ident)) => {
+        /// Enables the DAC and binds its channels to the given pins
+        ///
+        /// Pass a single pin to get a single channel or a tuple of pins to
+        /// get multiple channels.
+        pub fn $dacx<PINS>(_dac: $DACX, pins: PINS, apb1: &mut APB1) -> PINS::Channels
+        where
+            PINS: Pins<$DACX>,
+        {
+            apb1.enr().modify(|_, w| w.$dacXen().set_bit());
+            apb1.rstr().modify(|_, w| w.$dacXrst().set_bit());
+            apb1.rstr().modify(|_, w| w.$dacXrst().clear_bit());
+
+            pins.bind()
+        }
+    };
+}
+
+dac!(DAC1: (dac1, dac1en, dac1rst));
+
+dac_channel!(DAC1, PA4<Analog>, 0, 0b01, dhr8r1, dhr12r1, dhr12l1, dor1);
+dac_channel!(DAC1, PA5<Analog>, 16, 0b10, dhr8r2, dhr12r2, dhr12l2, dor2);
+
+impl Pins<DAC1> for (PA4<Analog>, PA5<Analog>) {
+    type Channels = (Dac<DAC1, PA4<Analog>>, Dac<DAC1, PA5<Analog>>);
+
+    fn bind(self) -> Self::Channels {
+        (self.0.bind(), self.1.bind())
+    }
+}
+
+#[cfg(any(
+    feature = "stm32f303x6",
+    feature = "stm32f303x8",
+    feature = "stm32f334",
+    feature = "stm32f373",
+    feature = "stm32f378"
+))]
+dac!(DAC2: (dac2, dac2en, dac2rst));
+
+#[cfg(any(
+    feature = "stm32f303x6",
+    feature = "stm32f303x8",
+    feature = "stm32f334",
+    feature = "stm32f373",
+    feature = "stm32f378"
+))]
+dac_channel!(DAC2, PA6<Analog>, 0, 0b01, dhr8r1, dhr12r1, dhr12l1, dor1);
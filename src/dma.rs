@@ -1,14 +1,50 @@
 //! Direct memory access (DMA) controller
 //!
-//! Currently DMA is only supported for STM32F303 MCUs.
+//! DMA1 is available on every device, DMA2 on the STM32F302xB/C/D/E,
+//! STM32F303xB/C/D/E, STM32F358, STM32F373, STM32F378 and STM32F398.
+//!
+//! Every peripheral request is bound to its channel through [`OnChannel`],
+//! following the request mapping tables of the reference manuals. Only the
+//! default mapping is supported. On devices without DMA2, the requests of
+//! TIM6, TIM7 and the DAC channels have to be remapped to DMA1 with
+//! [`SysCfg::remap_dma`] before a transfer is started.
+//!
+//! [`OnChannel`]: trait.OnChannel.html
+//! [`SysCfg::remap_dma`]: ../syscfg/struct.SysCfg.html#method.remap_dma
 
 // To learn about most of the ideas implemented here, check out the DMA section
 // of the Embedonomicon: https://docs.rust-embedded.org/embedonomicon/dma.html
 
+#[cfg(not(any(feature = "stm32f373", feature = "stm32f378")))]
+use crate::adc;
+#[cfg(any(
+    feature = "stm32f303x6",
+    feature = "stm32f303x8",
+    feature = "stm32f334",
+    feature = "stm32f373",
+    feature = "stm32f378"
+))]
+use crate::gpio::gpioa::PA6;
+#[cfg(any(feature = "stm32f373", feature = "stm32f378"))]
+use crate::sdadc;
+#[cfg(any(
+    feature = "stm32f303",
+    feature = "stm32f334",
+    feature = "stm32f373",
+    feature = "stm32f378"
+))]
+use crate::{
+    dac,
+    gpio::{
+        gpioa::{PA4, PA5},
+        Analog,
+    },
+};
 use crate::{
+    i2c,
     pac::{self, dma1::ch::cr},
     rcc::AHB,
-    serial,
+    serial, spi, timer,
 };
 use cast::u16;
 use core::{
@@ -35,7 +71,18 @@ pub trait Target {
     fn disable_dma(&mut self) {}
 }
 
-/// An in-progress one-shot DMA transfer
+/// Target of memory to memory transfers, which are possible on every channel
+///
+/// It is only used by [`Transfer::memcpy`](struct.Transfer.html#method.memcpy)
+/// and can not be passed to the other transfers, as there is no request
+/// source, which would drive them.
+pub struct Memory {
+    _0: (),
+}
+
+impl Target for Memory {}
+
+/// An in-progress DMA transfer
 pub struct Transfer<B, C: Channel, T: Target> {
     // This is always a `Some` outside of `drop`.
     inner: Option<TransferInner<B, C, T>>,
@@ -51,6 +98,36 @@ impl<B, C: Channel, T: Target> Transfer<B, C, T> {
     where
         B: WriteBuffer + 'static,
         T: OnChannel<C>,
+    {
+        Self::prepare_write(&mut buffer, &mut channel);
+        channel.set_circular_mode(false);
+
+        unsafe { Self::start(buffer, channel, target) }
+    }
+
+    /// Start a circular DMA write transfer.
+    ///
+    /// Whenever the buffer is full, the DMA starts over at its beginning,
+    /// until the transfer is stopped. [`is_complete`](#method.is_complete)
+    /// reports, if the buffer has been filled at least once.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer is longer than 65535 words.
+    pub fn start_write_circular(mut buffer: B, mut channel: C, target: T) -> Self
+    where
+        B: WriteBuffer + 'static,
+        T: OnChannel<C>,
+    {
+        Self::prepare_write(&mut buffer, &mut channel);
+        channel.set_circular_mode(true);
+
+        unsafe { Self::start(buffer, channel, target) }
+    }
+
+    fn prepare_write(buffer: &mut B, channel: &mut C)
+    where
+        B: WriteBuffer,
     {
         // NOTE(unsafe) We don't know the concrete type of `buffer` here, all
         // we can use are its `WriteBuffer` methods. Hence the only `&mut self`
@@ -63,8 +140,7 @@ impl<B, C: Channel, T: Target> Transfer<B, C, T> {
         channel.set_transfer_length(len);
         channel.set_word_size::<B::Word>();
         channel.set_direction(Direction::FromPeripheral);
-
-        unsafe { Self::start(buffer, channel, target) }
+        channel.set_mem2mem(false);
     }
 
     /// Start a DMA read transfer.
@@ -88,6 +164,8 @@ impl<B, C: Channel, T: Target> Transfer<B, C, T> {
         channel.set_transfer_length(len);
         channel.set_word_size::<B::Word>();
         channel.set_direction(Direction::FromMemory);
+        channel.set_circular_mode(false);
+        channel.set_mem2mem(false);
 
         unsafe { Self::start(buffer, channel, target) }
     }
@@ -98,10 +176,7 @@ impl<B, C: Channel, T: Target> Transfer<B, C, T> {
     ///
     /// - the given buffer will be valid for the duration of the transfer
     /// - the DMA channel is configured correctly for the given target and buffer
-    unsafe fn start(buffer: B, mut channel: C, mut target: T) -> Self
-    where
-        T: OnChannel<C>,
-    {
+    unsafe fn start(buffer: B, mut channel: C, mut target: T) -> Self {
         assert!(!channel.is_enabled());
 
         atomic::compiler_fence(Ordering::Release);
@@ -140,6 +215,31 @@ impl<B, C: Channel, T: Target> Transfer<B, C, T> {
     }
 }
 
+impl<S, D, C: Channel> Transfer<(S, D), C, Memory> {
+    /// Start a memory to memory transfer, which copies `src` into `dst`.
+    ///
+    /// Call [`wait`](#method.wait) to block until the copy is done.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffers differ in length or are longer than 65535 words.
+    pub fn memcpy(src: S, mut dst: D, mut channel: C) -> Self
+    where
+        S: ReadBuffer + 'static,
+        D: WriteBuffer<Word = S::Word> + 'static,
+    {
+        // NOTE(unsafe) See `start_read` and `prepare_write`, only the
+        // buffer methods are used.
+        let (src_ptr, src_len) = unsafe { src.read_buffer() };
+        let (dst_ptr, dst_len) = unsafe { dst.write_buffer() };
+        assert!(src_len == dst_len, "buffers differ in length");
+
+        prepare_memcpy::<_, S::Word>(&mut channel, src_ptr as u32, dst_ptr as u32, src_len);
+
+        unsafe { Self::start((src, dst), channel, Memory { _0: () }) }
+    }
+}
+
 impl<B, C: Channel, T: Target> Drop for Transfer<B, C, T> {
     fn drop(&mut self) {
         if let Some(inner) = self.inner.as_mut() {
@@ -148,6 +248,185 @@ impl<B, C: Channel, T: Target> Drop for Transfer<B, C, T> {
     }
 }
 
+/// DMA error
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The DMA has written into a half of the buffer, before it was read
+    Overrun,
+    /// The DMA has read a half of the buffer again, before it was refilled
+    Underrun,
+    /// The DMA signaled a transfer error, e.g. on a bus error
+    Transfer,
+}
+
+/// A half of the double buffer of a circular transfer
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Half {
+    /// The first buffer
+    First,
+    /// The second buffer
+    Second,
+}
+
+/// A circular DMA transfer with a double buffer
+///
+/// The DMA fills or reads both halves of the buffer over and over again.
+/// Whenever it is done with a half, the half can be borrowed with
+/// [`peek`](#method.peek) or refilled with [`fill`](#method.fill), while
+/// the DMA works on the other half.
+pub struct CircTransfer<B: 'static, C: Channel, T: Target> {
+    // This is always a `Some` outside of `drop`.
+    inner: Option<TransferInner<&'static mut [B; 2], C, T>>,
+    next: Half,
+}
+
+impl<B, C: Channel, T: Target> CircTransfer<B, C, T> {
+    /// Start a circular DMA write transfer into both halves of the buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer is longer than 65535 words.
+    pub fn start(mut buffer: &'static mut [B; 2], mut channel: C, target: T) -> Self
+    where
+        B: WriteTarget,
+        T: OnChannel<C>,
+    {
+        Transfer::<_, C, T>::prepare_write(&mut buffer, &mut channel);
+        channel.set_circular_mode(true);
+
+        unsafe { Self::start_circular(buffer, channel, target) }
+    }
+
+    /// Start a circular DMA read transfer from both halves of the buffer.
+    ///
+    /// The DMA starts with the first half right away, so both halves should
+    /// hold data already.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer is longer than 65535 words.
+    pub fn start_read(mut buffer: &'static mut [B; 2], mut channel: C, target: T) -> Self
+    where
+        B: WriteTarget,
+        T: OnChannel<C>,
+    {
+        // The halves are written by `fill` and read by the DMA
+        Transfer::<_, C, T>::prepare_write(&mut buffer, &mut channel);
+        channel.set_direction(Direction::FromMemory);
+        channel.set_circular_mode(true);
+
+        unsafe { Self::start_circular(buffer, channel, target) }
+    }
+
+    /// # Safety
+    ///
+    /// The DMA channel must be configured correctly for the given target and
+    /// buffer.
+    unsafe fn start_circular(buffer: &'static mut [B; 2], mut channel: C, mut target: T) -> Self {
+        assert!(!channel.is_enabled());
+
+        atomic::compiler_fence(Ordering::Release);
+
+        target.enable_dma();
+        channel.enable();
+
+        Self {
+            inner: Some(TransferInner {
+                buffer,
+                channel,
+                target,
+            }),
+            next: Half::First,
+        }
+    }
+
+    /// Call `f` with the next half of the buffer, which has been filled
+    ///
+    /// Returns `WouldBlock`, if the DMA is still writing into this half.
+    /// `Error::Overrun` is returned, if the DMA has started to overwrite the
+    /// half before it was read or before `f` returned. The transfer keeps
+    /// running and the next call continues with the other half.
+    pub fn peek<R, F>(&mut self, f: F) -> nb::Result<R, Error>
+    where
+        F: FnOnce(&B, Half) -> R,
+    {
+        self.next_half(|buffer, half| f(buffer, half), Error::Overrun)
+    }
+
+    /// Call `f` to refill the next half of the buffer, which has been read
+    ///
+    /// This is the counterpart of [`peek`](#method.peek) for a transfer
+    /// started with [`start_read`](#method.start_read). Returns
+    /// `WouldBlock`, if the DMA is still reading this half.
+    /// `Error::Underrun` is returned, if the DMA has started to read the half
+    /// again before it was refilled or before `f` returned. The transfer
+    /// keeps running and the next call continues with the other half.
+    pub fn fill<R, F>(&mut self, f: F) -> nb::Result<R, Error>
+    where
+        F: FnOnce(&mut B, Half) -> R,
+    {
+        self.next_half(f, Error::Underrun)
+    }
+
+    /// Calls `f` with the next half, once the DMA is done with it
+    fn next_half<R, F>(&mut self, f: F, late: Error) -> nb::Result<R, Error>
+    where
+        F: FnOnce(&mut B, Half) -> R,
+    {
+        let inner = self.inner.as_mut().unwrap();
+        let half = self.next;
+        let (index, done, other) = match half {
+            Half::First => (0, Event::HalfTransfer, Event::TransferComplete),
+            Half::Second => (1, Event::TransferComplete, Event::HalfTransfer),
+        };
+        let other_half = match half {
+            Half::First => Half::Second,
+            Half::Second => Half::First,
+        };
+
+        // The DMA is done with the other half as well and wrapped around into
+        // this one, so only the other half can still be used
+        if inner.channel.event_occurred(other) {
+            inner.channel.clear_event(done);
+            self.next = other_half;
+            return Err(nb::Error::Other(late));
+        }
+
+        if !inner.channel.event_occurred(done) {
+            return Err(nb::Error::WouldBlock);
+        }
+        inner.channel.clear_event(done);
+
+        atomic::compiler_fence(Ordering::Acquire);
+        let result = f(&mut inner.buffer[index], half);
+        atomic::compiler_fence(Ordering::SeqCst);
+
+        self.next = other_half;
+        if inner.channel.event_occurred(other) {
+            Err(nb::Error::Other(late))
+        } else {
+            Ok(result)
+        }
+    }
+
+    /// Stop this transfer and return ownership over its parts
+    pub fn stop(mut self) -> (&'static mut [B; 2], C, T) {
+        let mut inner = self.inner.take().unwrap();
+        inner.stop();
+
+        (inner.buffer, inner.channel, inner.target)
+    }
+}
+
+impl<B, C: Channel, T: Target> Drop for CircTransfer<B, C, T> {
+    fn drop(&mut self) {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.stop();
+        }
+    }
+}
+
 /// This only exists so we can implement `Drop` for `Transfer`.
 struct TransferInner<B, C, T> {
     buffer: B,
@@ -545,6 +824,59 @@ pub trait Channel: private::Channel {
         self.ch().cr.modify(|_, w| w.dir().variant(dir));
     }
 
+    /// Enable or disable the circular mode
+    ///
+    /// In circular mode, the channel starts over at the beginning of the
+    /// memory area, whenever the transfer length is reached.
+    fn set_circular_mode(&mut self, circular: bool) {
+        self.ch().cr.modify(|_, w| w.circ().bit(circular));
+    }
+
+    /// Enable or disable the memory to memory mode
+    ///
+    /// In memory to memory mode, the transfer does not wait for requests of
+    /// a peripheral. The peripheral address is used as a second memory address.
+    fn set_mem2mem(&mut self, mem2mem: bool) {
+        self.ch().cr.modify(|_, w| w.mem2mem().bit(mem2mem));
+    }
+
+    /// Copy `src` into `dst` and block until the copy is done
+    ///
+    /// A transfer error aborts the copy and is reported as `Error::Transfer`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this channel is enabled, if the buffers differ in length or
+    /// are longer than 65535 words.
+    fn memcpy<W: Word>(&mut self, src: &[W], dst: &mut [W]) -> Result<(), Error> {
+        assert!(src.len() == dst.len(), "buffers differ in length");
+        if src.is_empty() {
+            return Ok(());
+        }
+
+        prepare_memcpy::<_, W>(
+            self,
+            src.as_ptr() as u32,
+            dst.as_mut_ptr() as u32,
+            src.len(),
+        );
+
+        atomic::compiler_fence(Ordering::Release);
+        self.enable();
+        let result = loop {
+            if self.event_occurred(Event::TransferError) {
+                break Err(Error::Transfer);
+            }
+            if self.event_occurred(Event::TransferComplete) {
+                break Ok(());
+            }
+        };
+        self.disable();
+        atomic::compiler_fence(Ordering::SeqCst);
+
+        result
+    }
+
     /// Enable the interrupt for the given event
     fn listen(&mut self, event: Event) {
         use Event::*;
@@ -592,6 +924,22 @@ pub trait Channel: private::Channel {
     }
 }
 
+/// Configure a disabled channel to copy `len` words from `src` to `dst`
+fn prepare_memcpy<C, W>(channel: &mut C, src: u32, dst: u32, len: usize)
+where
+    C: Channel + ?Sized,
+{
+    let len = u16(len).expect("buffer is too large");
+
+    channel.set_peripheral_address(src, Increment::Enable);
+    channel.set_memory_address(dst, Increment::Enable);
+    channel.set_transfer_length(len);
+    channel.set_word_size::<W>();
+    channel.set_direction(Direction::FromPeripheral);
+    channel.set_circular_mode(false);
+    channel.set_mem2mem(true);
+}
+
 mod private {
     use crate::pac;
 
@@ -705,10 +1053,15 @@ dma!(
 );
 
 #[cfg(any(
+    feature = "stm32f302",
     feature = "stm32f303xb",
     feature = "stm32f303xc",
     feature = "stm32f303xd",
-    feature = "stm32f303xe"
+    feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f373",
+    feature = "stm32f378",
+    feature = "stm32f398"
 ))]
 dma!(
     DMA2, dma2, dma2en,
@@ -738,12 +1091,242 @@ macro_rules! on_channel {
     };
 }
 
-#[cfg(feature = "stm32f303")]
 on_channel!(dma1,
     serial::Rx<pac::USART1> => C5,
     serial::Tx<pac::USART1> => C4,
     serial::Rx<pac::USART2> => C6,
     serial::Tx<pac::USART2> => C7,
+    i2c::TxDma<pac::I2C1> => C6,
+    i2c::RxDma<pac::I2C1> => C7,
+    timer::Timer<pac::TIM2> => C2,
+    timer::Timer<pac::TIM15> => C5,
+    timer::Timer<pac::TIM16> => C3,
+    timer::Timer<pac::TIM17> => C1,
+);
+
+#[cfg(any(
+    feature = "stm32f301",
+    feature = "stm32f318",
+    feature = "stm32f303",
+    feature = "stm32f373",
+    feature = "stm32f378",
+    feature = "stm32f328",
+    feature = "stm32f358",
+    feature = "stm32f398"
+))]
+on_channel!(dma1,
     serial::Rx<pac::USART3> => C3,
     serial::Tx<pac::USART3> => C2,
 );
+
+#[cfg(not(any(feature = "stm32f373", feature = "stm32f378")))]
+on_channel!(dma1,
+    adc::Adc<pac::ADC1> => C1,
+    timer::Timer<pac::TIM1> => C5,
+);
+
+#[cfg(not(any(feature = "stm32f301", feature = "stm32f318")))]
+on_channel!(dma1,
+    spi::RxDma<pac::SPI1> => C2,
+    spi::TxDma<pac::SPI1> => C3,
+);
+
+#[cfg(not(feature = "stm32f334"))]
+on_channel!(dma1,
+    spi::RxDma<pac::SPI2> => C4,
+    spi::TxDma<pac::SPI2> => C5,
+    i2c::TxDma<pac::I2C2> => C4,
+    i2c::RxDma<pac::I2C2> => C5,
+);
+
+#[cfg(any(
+    feature = "stm32f303",
+    feature = "stm32f328",
+    feature = "stm32f334",
+    feature = "stm32f358",
+    feature = "stm32f373",
+    feature = "stm32f378",
+    feature = "stm32f398"
+))]
+on_channel!(dma1,
+    timer::Timer<pac::TIM3> => C3,
+);
+
+#[cfg(any(
+    feature = "stm32f303",
+    feature = "stm32f328",
+    feature = "stm32f358",
+    feature = "stm32f373",
+    feature = "stm32f378",
+    feature = "stm32f398"
+))]
+on_channel!(dma1,
+    timer::Timer<pac::TIM4> => C7,
+);
+
+// Requests, which are remapped to DMA1 on devices without DMA2
+
+#[cfg(any(
+    feature = "stm32f301",
+    feature = "stm32f318",
+    feature = "stm32f303x6",
+    feature = "stm32f303x8",
+    feature = "stm32f334",
+    feature = "stm32f328"
+))]
+on_channel!(dma1,
+    timer::Timer<pac::TIM6> => C3,
+);
+
+#[cfg(any(
+    feature = "stm32f303x6",
+    feature = "stm32f303x8",
+    feature = "stm32f334",
+    feature = "stm32f328"
+))]
+on_channel!(dma1,
+    adc::Adc<pac::ADC2> => C2,
+    timer::Timer<pac::TIM7> => C4,
+);
+
+#[cfg(any(
+    feature = "stm32f303x6",
+    feature = "stm32f303x8",
+    feature = "stm32f334"
+))]
+on_channel!(dma1,
+    dac::Dac<pac::DAC1, PA4<Analog>> => C3,
+    dac::Dac<pac::DAC1, PA5<Analog>> => C4,
+);
+
+#[cfg(any(
+    feature = "stm32f303x6",
+    feature = "stm32f303x8",
+    feature = "stm32f334"
+))]
+on_channel!(dma1,
+    dac::Dac<pac::DAC2, PA6<Analog>> => C5,
+);
+
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f373",
+    feature = "stm32f378",
+    feature = "stm32f398"
+))]
+on_channel!(dma2,
+    spi::RxDma<pac::SPI3> => C1,
+    spi::TxDma<pac::SPI3> => C2,
+    timer::Timer<pac::TIM6> => C3,
+);
+
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398"
+))]
+on_channel!(dma2,
+    adc::Adc<pac::ADC2> => C1,
+    serial::Rx<pac::UART4> => C3,
+    serial::Tx<pac::UART4> => C5,
+);
+
+#[cfg(any(
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398"
+))]
+on_channel!(dma2,
+    adc::Adc<pac::ADC4> => C2,
+    adc::Adc<pac::ADC3> => C5,
+    timer::Timer<pac::TIM8> => C1,
+);
+
+#[cfg(any(
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f373",
+    feature = "stm32f378",
+    feature = "stm32f398"
+))]
+on_channel!(dma2,
+    timer::Timer<pac::TIM7> => C4,
+);
+
+#[cfg(any(
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f373",
+    feature = "stm32f378"
+))]
+on_channel!(dma2,
+    dac::Dac<pac::DAC1, PA4<Analog>> => C3,
+    dac::Dac<pac::DAC1, PA5<Analog>> => C4,
+);
+
+#[cfg(any(
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f398"
+))]
+on_channel!(dma2,
+    spi::RxDma<pac::SPI4> => C4,
+    spi::TxDma<pac::SPI4> => C5,
+);
+
+// The extension blocks of the I2S share the requests of their SPI
+
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398"
+))]
+on_channel!(dma1,
+    spi::RxDma<pac::I2S2EXT> => C4,
+    spi::TxDma<pac::I2S2EXT> => C5,
+);
+
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398"
+))]
+on_channel!(dma2,
+    spi::RxDma<pac::I2S3EXT> => C1,
+    spi::TxDma<pac::I2S3EXT> => C2,
+);
+
+#[cfg(any(feature = "stm32f373", feature = "stm32f378"))]
+on_channel!(dma2,
+    timer::Timer<pac::TIM5> => C2,
+    timer::Timer<pac::TIM18> => C5,
+    dac::Dac<pac::DAC2, PA6<Analog>> => C5,
+    sdadc::SdAdc<pac::SDADC1> => C3,
+    sdadc::SdAdc<pac::SDADC2> => C4,
+    sdadc::SdAdc<pac::SDADC3> => C5,
+);
@@ -39,7 +39,9 @@ use crate::hal::digital::v2::InputPin;
 use crate::hal::digital::v2::OutputPin;
 #[cfg(feature = "unproven")]
 use crate::hal::digital::v2::StatefulOutputPin;
+use crate::pac::EXTI;
 use crate::rcc::AHB;
+use crate::syscfg::SysCfg;
 
 /// Extension trait to split a GPIO peripheral in independent pins and registers
 pub trait GpioExt {
@@ -123,12 +125,125 @@ pub struct AF14;
 /// Alternate function 15 (type state)
 pub struct AF15;
 
+/// Edge of the input signal, which triggers an external interrupt
+pub enum Edge {
+    /// Trigger on the rising edge
+    Rising,
+    /// Trigger on the falling edge
+    Falling,
+    /// Trigger on both the rising and the falling edge
+    Both,
+}
+
+mod private {
+    /// Pin methods private to this module
+    pub trait Exti {
+        /// Index of the GPIO port (GPIOA = 0, GPIOB = 1, ...)
+        fn port_index(&self) -> u8;
+        /// Index of the pin in its port, which is also the number of its EXTI line
+        fn pin_index(&self) -> u8;
+    }
+}
+
+/// External interrupt functionality of input pins
+///
+/// Every pin can be routed to the EXTI line with the same number, but only one
+/// port can be connected to a line at a time, e.g. either PA0 or PB0 can be
+/// the source of EXTI0.
+///
+/// ```
+/// let mut syscfg = dp.SYSCFG.constrain(&mut rcc.apb2);
+/// let mut exti = dp.EXTI;
+///
+/// let mut button = gpioa.pa0.into_pull_down_input(&mut gpioa.moder, &mut gpioa.pupdr);
+/// button.make_interrupt_source(&mut syscfg);
+/// button.trigger_on_edge(&mut exti, Edge::Rising);
+/// button.enable_interrupt(&mut exti);
+/// ```
+pub trait ExtiPin: private::Exti {
+    /// Connects the EXTI line of this pin to the port of this pin
+    fn make_interrupt_source(&mut self, syscfg: &mut SysCfg) {
+        let port = u32::from(self.port_index());
+        let i = self.pin_index();
+        let offset = 4 * (i % 4);
+        let mask = !(0b1111 << offset);
+        // NOTE(unsafe) only the 4 bits of the EXTI line of this pin are modified
+        unsafe {
+            match i / 4 {
+                0 => syscfg
+                    .exticr1()
+                    .modify(|r, w| w.bits((r.bits() & mask) | (port << offset))),
+                1 => syscfg
+                    .exticr2()
+                    .modify(|r, w| w.bits((r.bits() & mask) | (port << offset))),
+                2 => syscfg
+                    .exticr3()
+                    .modify(|r, w| w.bits((r.bits() & mask) | (port << offset))),
+                3 => syscfg
+                    .exticr4()
+                    .modify(|r, w| w.bits((r.bits() & mask) | (port << offset))),
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    /// Selects the edge of the input signal, which triggers the interrupt
+    fn trigger_on_edge(&mut self, exti: &mut EXTI, edge: Edge) {
+        let line = 1 << self.pin_index();
+        // NOTE(unsafe) only the bit of the EXTI line of this pin is modified
+        unsafe {
+            match edge {
+                Edge::Rising => {
+                    exti.rtsr1.modify(|r, w| w.bits(r.bits() | line));
+                    exti.ftsr1.modify(|r, w| w.bits(r.bits() & !line));
+                }
+                Edge::Falling => {
+                    exti.rtsr1.modify(|r, w| w.bits(r.bits() & !line));
+                    exti.ftsr1.modify(|r, w| w.bits(r.bits() | line));
+                }
+                Edge::Both => {
+                    exti.rtsr1.modify(|r, w| w.bits(r.bits() | line));
+                    exti.ftsr1.modify(|r, w| w.bits(r.bits() | line));
+                }
+            }
+        }
+    }
+
+    /// Unmasks the interrupt of the EXTI line of this pin
+    fn enable_interrupt(&mut self, exti: &mut EXTI) {
+        let line = 1 << self.pin_index();
+        // NOTE(unsafe) only the bit of the EXTI line of this pin is modified
+        exti.imr1.modify(|r, w| unsafe { w.bits(r.bits() | line) });
+    }
+
+    /// Masks the interrupt of the EXTI line of this pin
+    fn disable_interrupt(&mut self, exti: &mut EXTI) {
+        let line = 1 << self.pin_index();
+        // NOTE(unsafe) only the bit of the EXTI line of this pin is modified
+        exti.imr1.modify(|r, w| unsafe { w.bits(r.bits() & !line) });
+    }
+
+    /// Clears the interrupt pending bit of the EXTI line of this pin
+    fn clear_interrupt_pending_bit(&mut self) {
+        // NOTE(unsafe) atomic write to a stateless register,
+        // writing zeros to the other lines has no effect
+        unsafe { (*EXTI::ptr()).pr1.write(|w| w.bits(1 << self.pin_index())) };
+    }
+
+    /// Returns true, if the interrupt of the EXTI line of this pin is pending
+    fn check_interrupt(&self) -> bool {
+        // NOTE(unsafe) atomic read with no side effects
+        unsafe { (*EXTI::ptr()).pr1.read().bits() & (1 << self.pin_index()) != 0 }
+    }
+}
+
 macro_rules! gpio {
     ([
         $({
             devices: [$($device:expr,)+],
             devices_except: [$($device_except:expr,)*],
             GPIO: $GPIOX:ident,
+            port_index: $port_index:expr,
             gpio: $gpiox:ident,
             gpio_mapped: $gpioy:ident,
             gpio_mapped_ioenr: $iopxenr:ident,
@@ -285,6 +400,27 @@ macro_rules! gpio {
         #[cfg(feature = "unproven")]
         impl <MODE> toggleable::Default for PXx<Output<MODE>> {}
 
+        impl<MODE> private::Exti for PXx<Input<MODE>> {
+            fn port_index(&self) -> u8 {
+                match &self.gpio {
+                    $(
+                        #[cfg(all(any(
+                            $(feature = $device,)+
+                        ), not(any(
+                            $(feature = $device_except,)*
+                        ))))]
+                        Gpio::$GPIOX => $port_index,
+                    )+
+                }
+            }
+
+            fn pin_index(&self) -> u8 {
+                self.i
+            }
+        }
+
+        impl<MODE> ExtiPin for PXx<Input<MODE>> {}
+
         $(
             /// GPIO
             #[cfg(all(any(
@@ -311,8 +447,9 @@ macro_rules! gpio {
                 use super::{
                     Floating, GpioExt, Input, OpenDrain, Output, Analog,
                     PullDown, PullUp, PushPull,
-                    PXx, Gpio,
+                    PXx, Gpio, ExtiPin,
                 };
+                use super::private;
 
                 /// GPIO parts
                 pub struct Parts {
@@ -494,6 +631,18 @@ macro_rules! gpio {
                 #[cfg(feature = "unproven")]
                 impl<MODE> toggleable::Default for $PXx<Output<MODE>> {}
 
+                impl<MODE> private::Exti for $PXx<Input<MODE>> {
+                    fn port_index(&self) -> u8 {
+                        $port_index
+                    }
+
+                    fn pin_index(&self) -> u8 {
+                        self.i
+                    }
+                }
+
+                impl<MODE> ExtiPin for $PXx<Input<MODE>> {}
+
                 $(
                     /// Pin
                     pub struct $PXi<MODE> {
@@ -693,6 +842,18 @@ macro_rules! gpio {
 
                     #[cfg(feature = "unproven")]
                     impl<MODE> toggleable::Default for $PXi<Output<MODE>> {}
+
+                    impl<MODE> private::Exti for $PXi<Input<MODE>> {
+                        fn port_index(&self) -> u8 {
+                            $port_index
+                        }
+
+                        fn pin_index(&self) -> u8 {
+                            $i
+                        }
+                    }
+
+                    impl<MODE> ExtiPin for $PXi<Input<MODE>> {}
                 )+
             }
         )+
@@ -715,6 +876,7 @@ gpio!([
         ],
         devices_except: [],
         GPIO: GPIOA,
+        port_index: 0,
         gpio: gpioa,
         gpio_mapped: gpioa,
         gpio_mapped_ioenr: iopaen,
@@ -939,6 +1101,7 @@ gpio!([
         ],
         devices_except: [],
         GPIO: GPIOB,
+        port_index: 1,
         gpio: gpiob,
         gpio_mapped: gpiob,
         gpio_mapped_ioenr: iopben,
@@ -1126,6 +1289,7 @@ gpio!([
         ],
         devices_except: [],
         GPIO: GPIOB,
+        port_index: 1,
         gpio: gpiob,
         gpio_mapped: gpiob,
         gpio_mapped_ioenr: iopben,
@@ -1277,6 +1441,7 @@ gpio!([
         ],
         devices_except: [],
         GPIO: GPIOC,
+        port_index: 2,
         gpio: gpioc,
         gpio_mapped: gpioc,
         gpio_mapped_ioenr: iopcen,
@@ -1394,6 +1559,7 @@ gpio!([
         ],
         devices_except: [],
         GPIO: GPIOC,
+        port_index: 2,
         gpio: gpioc,
         gpio_mapped: gpiob,
         gpio_mapped_ioenr: iopcen,
@@ -1510,6 +1676,7 @@ gpio!([
         ],
         devices_except: [],
         GPIO: GPIOD,
+        port_index: 3,
         gpio: gpiod,
         gpio_mapped: gpioc,
         gpio_mapped_ioenr: iopden,
@@ -1532,6 +1699,7 @@ gpio!([
             "stm32f303xe",
         ],
         GPIO: GPIOD,
+        port_index: 3,
         gpio: gpiod,
         gpio_mapped: gpiob,
         gpio_mapped_ioenr: iopden,
@@ -1550,6 +1718,7 @@ gpio!([
         ],
         devices_except: [],
         GPIO: GPIOD,
+        port_index: 3,
         gpio: gpiod,
         gpio_mapped: gpioc,
         gpio_mapped_ioenr: iopden,
@@ -1592,6 +1761,7 @@ gpio!([
         ],
         devices_except: [],
         GPIO: GPIOD,
+        port_index: 3,
         gpio: gpiod,
         gpio_mapped: gpiob,
         gpio_mapped_ioenr: iopden,
@@ -1712,6 +1882,7 @@ gpio!([
         ],
         devices_except: [],
         GPIO: GPIOE,
+        port_index: 4,
         gpio: gpioe,
         gpio_mapped: gpiob,
         gpio_mapped_ioenr: iopeen,
@@ -1855,6 +2026,7 @@ gpio!([
         ],
         devices_except: [],
         GPIO: GPIOE,
+        port_index: 4,
         gpio: gpioe,
         gpio_mapped: gpioc,
         gpio_mapped_ioenr: iopeen,
@@ -1979,6 +2151,7 @@ gpio!([
         ],
         devices_except: [],
         GPIO: GPIOF,
+        port_index: 5,
         gpio: gpiof,
         gpio_mapped: gpioc,
         gpio_mapped_ioenr: iopfen,
@@ -2007,6 +2180,7 @@ gpio!([
             "stm32f303xe",
         ],
         GPIO: GPIOF,
+        port_index: 5,
         gpio: gpiof,
         gpio_mapped: gpiob,
         gpio_mapped_ioenr: iopfen,
@@ -2025,6 +2199,7 @@ gpio!([
         ],
         devices_except: [],
         GPIO: GPIOF,
+        port_index: 5,
         gpio: gpiof,
         gpio_mapped: gpiob,
         gpio_mapped_ioenr: iopfen,
@@ -2066,6 +2241,7 @@ gpio!([
         ],
         devices_except: [],
         GPIO: GPIOF,
+        port_index: 5,
         gpio: gpiof,
         gpio_mapped: gpioc,
         gpio_mapped_ioenr: iopfen,
@@ -2093,6 +2269,7 @@ gpio!([
         ],
         devices_except: [],
         GPIO: GPIOF,
+        port_index: 5,
         gpio: gpiof,
         gpio_mapped: gpiob,
         gpio_mapped_ioenr: iopfen,
@@ -2163,6 +2340,7 @@ gpio!([
         ],
         devices_except: [],
         GPIO: GPIOF,
+        port_index: 5,
         gpio: gpiof,
         gpio_mapped: gpioc,
         gpio_mapped_ioenr: iopfen,
@@ -2208,6 +2386,7 @@ gpio!([
         ],
         devices_except: [],
         GPIO: GPIOF,
+        port_index: 5,
         gpio: gpiof,
         gpio_mapped: gpioc,
         gpio_mapped_ioenr: iopfen,
@@ -2310,6 +2489,7 @@ gpio!([
         ],
         devices_except: [],
         GPIO: GPIOG,
+        port_index: 6,
         gpio: gpiog,
         gpio_mapped: gpiob,
         gpio_mapped_ioenr: iopgen,
@@ -2393,6 +2573,7 @@ gpio!([
         ],
         devices_except: [],
         GPIO: GPIOH,
+        port_index: 7,
         gpio: gpioh,
         gpio_mapped: gpiob,
         gpio_mapped_ioenr: iophen,
@@ -1,10 +1,28 @@
 //! Inter-Integrated Circuit (I2C) bus
 
-use crate::pac::{I2C1, I2C2};
+use core::{cmp, marker::PhantomData};
+
+use crate::pac::{i2c1, I2C1, I2C2};
 use cast::u8;
+use cortex_m::interrupt;
 
+use crate::dma;
+#[cfg(any(
+    feature = "stm32f301",
+    feature = "stm32f318",
+    feature = "stm32f302",
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f373",
+    feature = "stm32f378",
+    feature = "stm32f358",
+    feature = "stm32f398"
+))]
+use crate::gpio::gpioa::PA8;
 use crate::gpio::gpioa::{PA10, PA9};
-use crate::gpio::gpiob::{PB6, PB7, PB8, PB9};
+use crate::gpio::gpiob::{PB5, PB6, PB7, PB8, PB9};
 #[cfg(any(
     feature = "stm32f302",
     feature = "stm32f334",
@@ -23,7 +41,8 @@ use crate::gpio::gpiof::{PF0, PF1};
 use crate::gpio::AF4;
 use crate::hal::blocking::i2c::{Read, Write, WriteRead};
 use crate::rcc::{Clocks, APB1};
-use crate::time::Hertz;
+use crate::syscfg::SysCfg;
+use crate::time::{Hertz, KiloHertz, MegaHertz, MilliSeconds};
 
 /// I2C error
 #[derive(Debug)]
@@ -33,10 +52,198 @@ pub enum Error {
     Bus,
     /// Arbitration loss
     Arbitration,
-    // Overrun, // slave mode only
-    // Pec, // SMBUS mode only
-    // Timeout, // SMBUS mode only
-    // Alert, // SMBUS mode only
+    /// The slave did not acknowledge its address or a byte
+    Nack,
+    /// A received byte was not read in time, or no byte was sent in time,
+    /// while clock stretching is disabled (slave mode only)
+    Overrun,
+    /// The received PEC did not match the calculated one
+    Pec,
+    /// SCL was held low for longer than the configured timeout
+    Timeout,
+}
+
+/// Invalid I2C timing configuration
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum TimingError {
+    /// The speed is zero or exceeds 1 MHz
+    Speed,
+    /// The rise or fall time exceeds the maximum of the mode
+    RiseFallTime,
+    /// The digital filter is longer than 15 cycles
+    DigitalFilter,
+    /// The timing requirements can not be met with the I2C clock
+    Clock,
+}
+
+/// Timing configuration of an I2C master
+///
+/// The rise and fall times of SCL and SDA depend on the pull-ups and the bus
+/// capacitance and default to 0 ns. Measured or estimated values keep the
+/// speed and the data setup and hold times within the limits of the mode.
+/// The analog noise filter is enabled and the digital one is disabled by
+/// default.
+#[derive(Clone, Copy, Debug)]
+pub struct TimingConfig {
+    freq: u32,
+    rise_time: u32,
+    fall_time: u32,
+    digital_filter: u8,
+    analog_filter: bool,
+}
+
+impl TimingConfig {
+    /// Creates a configuration for the bus speed
+    pub fn new<F>(freq: F) -> Self
+    where
+        F: Into<Hertz>,
+    {
+        TimingConfig {
+            freq: freq.into().0,
+            rise_time: 0,
+            fall_time: 0,
+            digital_filter: 0,
+            analog_filter: true,
+        }
+    }
+
+    /// Sets the rise time of SCL and SDA in ns
+    pub fn rise_time(mut self, ns: u32) -> Self {
+        self.rise_time = ns;
+        self
+    }
+
+    /// Sets the fall time of SCL and SDA in ns
+    pub fn fall_time(mut self, ns: u32) -> Self {
+        self.fall_time = ns;
+        self
+    }
+
+    /// Sets the length of the digital noise filter in I2C clock cycles, or
+    /// disables it with 0
+    pub fn digital_filter(mut self, cycles: u8) -> Self {
+        self.digital_filter = cycles;
+        self
+    }
+
+    /// Enables or disables the analog noise filter
+    pub fn analog_filter(mut self, enable: bool) -> Self {
+        self.analog_filter = enable;
+        self
+    }
+
+    /// Computes PRESC, SCLL, SCLH, SDADEL and SCLDEL for the I2C clock
+    fn timingr(&self, i2cclk: u32) -> Result<(u8, u8, u8, u8, u8), TimingError> {
+        // Limits of the I2C specification in ns:
+        // max. rise time, max. fall time, min. low period, min. high period,
+        // min. data setup time, max. data hold time
+        let (rise_max, fall_max, t_low_min, t_high_min, su_dat_min, hd_dat_max) = match self.freq {
+            0 => return Err(TimingError::Speed),
+            1..=100_000 => (1000, 300, 4700, 4000, 250, 3450),
+            100_001..=400_000 => (300, 300, 1300, 600, 100, 900),
+            400_001..=1_000_000 => (120, 120, 500, 260, 50, 450),
+            _ => return Err(TimingError::Speed),
+        };
+        if self.rise_time > rise_max || self.fall_time > fall_max {
+            return Err(TimingError::RiseFallTime);
+        }
+        if self.digital_filter > 15 {
+            return Err(TimingError::DigitalFilter);
+        }
+
+        // Delays of the analog filter in ns
+        let (af_min, af_max) = if self.analog_filter {
+            (50, 260)
+        } else {
+            (0, 0)
+        };
+        let dnf = u32::from(self.digital_filter);
+        // Converts ns into I2C clock cycles, rounded up
+        let cycles =
+            |ns: u32| ((u64::from(ns) * u64::from(i2cclk) + 999_999_999) / 1_000_000_000) as u32;
+
+        // t_SDADEL >= t_f - t_AF(min) - t_DNF - 3 * t_I2CCLK
+        // t_SDADEL <= t_HD;DAT(max) - t_r - t_AF(max) - t_DNF - 4 * t_I2CCLK
+        let sdadel_min = cycles(self.fall_time.saturating_sub(af_min)).saturating_sub(dnf + 3);
+        let sdadel_max = (u64::from(hd_dat_max - self.rise_time - af_max) * u64::from(i2cclk)
+            / 1_000_000_000) as u32;
+        let sdadel_max = match sdadel_max.checked_sub(dnf + 4) {
+            Some(sdadel_max) => sdadel_max,
+            None => return Err(TimingError::Clock),
+        };
+        // t_SCLDEL >= t_r + t_SU;DAT(min)
+        let scldel_min = cycles(self.rise_time + su_dat_min);
+
+        // t_SCL = t_SYNC1 + t_SYNC2 + (SCLL + 1 + SCLH + 1) * t_PRESC
+        // t_SYNC = t_AF + t_DNF + 2 * t_I2CCLK plus the fall or rise time
+        let sync = cycles(af_min) + dnf + 2;
+        let period = (i2cclk + self.freq - 1) / self.freq;
+        let available = match period.checked_sub(
+            cycles(self.fall_time + af_min) + cycles(self.rise_time + af_min) + 2 * (dnf + 2),
+        ) {
+            Some(available) => available,
+            None => return Err(TimingError::Clock),
+        };
+        // The low and high periods include the synchronization
+        let low_min = cycles(t_low_min).saturating_sub(sync);
+        let high_min = cycles(t_high_min).saturating_sub(sync);
+
+        for presc in 0..16 {
+            let t_presc = presc + 1;
+
+            let sdadel = (sdadel_min + t_presc - 1) / t_presc;
+            if sdadel > 15 || sdadel * t_presc > sdadel_max {
+                continue;
+            }
+            let scldel = ((scldel_min + t_presc - 1) / t_presc).saturating_sub(1);
+            if scldel > 15 {
+                continue;
+            }
+
+            // SCLL + 1 and SCLH + 1
+            let low = (low_min + t_presc - 1) / t_presc;
+            let high = (high_min + t_presc - 1) / t_presc;
+            let total = (available + t_presc - 1) / t_presc;
+            if low + high > total {
+                continue;
+            }
+            // Share the spare time between the low and high periods
+            let spare = total - low - high;
+            let (low, high) = (low + spare - spare / 2, high + spare / 2);
+            if low > 256 || high > 256 {
+                continue;
+            }
+
+            return Ok((
+                presc as u8,
+                (low - 1) as u8,
+                (high - 1) as u8,
+                sdadel as u8,
+                scldel as u8,
+            ));
+        }
+
+        Err(TimingError::Clock)
+    }
+}
+
+impl From<Hertz> for TimingConfig {
+    fn from(freq: Hertz) -> Self {
+        TimingConfig::new(freq)
+    }
+}
+
+impl From<KiloHertz> for TimingConfig {
+    fn from(freq: KiloHertz) -> Self {
+        TimingConfig::new(freq)
+    }
+}
+
+impl From<MegaHertz> for TimingConfig {
+    fn from(freq: MegaHertz) -> Self {
+        TimingConfig::new(freq)
+    }
 }
 
 // FIXME these should be "closed" traits
@@ -74,12 +281,271 @@ unsafe impl SdaPin<I2C1> for PB9<AF4> {}
 unsafe impl SdaPin<I2C2> for PA10<AF4> {}
 unsafe impl SdaPin<I2C2> for PF0<AF4> {}
 
+/// SCL or SDA pin with its own Fast-mode Plus drive -- DO NOT IMPLEMENT THIS TRAIT
+///
+/// The Fast-mode Plus drive of these pins is enabled in SYSCFG independently
+/// of the I2C peripheral, see also
+/// [`I2c::set_fast_mode_plus`](struct.I2c.html#method.set_fast_mode_plus).
+pub unsafe trait FastModePlusPin {
+    #[doc(hidden)]
+    const FMP: u8;
+
+    /// Enables or disables the Fast-mode Plus drive of this pin
+    fn set_fast_mode_plus(&mut self, enable: bool, syscfg: &mut SysCfg) {
+        set_fast_mode_plus(syscfg, Self::FMP, enable);
+    }
+}
+
+// I2C_PBx_FMP
+unsafe impl FastModePlusPin for PB6<AF4> {
+    const FMP: u8 = 16;
+}
+unsafe impl FastModePlusPin for PB7<AF4> {
+    const FMP: u8 = 17;
+}
+unsafe impl FastModePlusPin for PB8<AF4> {
+    const FMP: u8 = 18;
+}
+unsafe impl FastModePlusPin for PB9<AF4> {
+    const FMP: u8 = 19;
+}
+
+/// SMBA pin -- DO NOT IMPLEMENT THIS TRAIT
+pub unsafe trait SmbaPin<I2C> {}
+
+unsafe impl SmbaPin<I2C1> for PB5<AF4> {}
+
+#[cfg(any(
+    feature = "stm32f301",
+    feature = "stm32f318",
+    feature = "stm32f302",
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f373",
+    feature = "stm32f378",
+    feature = "stm32f358",
+    feature = "stm32f398"
+))]
+unsafe impl SmbaPin<I2C2> for PA8<AF4> {}
+
 /// I2C peripheral operating in master mode
 pub struct I2c<I2C, PINS> {
     i2c: I2C,
     pins: PINS,
 }
 
+/// DMA requests of the I2C receiver
+pub struct RxDma<I2C> {
+    _i2c: PhantomData<I2C>,
+}
+
+/// DMA requests of the I2C transmitter
+pub struct TxDma<I2C> {
+    _i2c: PhantomData<I2C>,
+}
+
+/// An I2C write or read using DMA
+///
+/// Returned by `I2c::write_dma` and `I2c::read_dma`.
+pub struct DmaTransfer<I2C, PINS, B, C, T>
+where
+    C: dma::Channel,
+    T: dma::Target,
+{
+    i2c: I2c<I2C, PINS>,
+    transfer: dma::Transfer<B, C, T>,
+    /// Bytes after the current chunk
+    remaining: usize,
+}
+
+/// An I2C write followed by a read with a repeated start using DMA
+///
+/// Returned by `I2c::write_read_dma`.
+pub struct DmaWriteRead<I2C, PINS, TXB, RXB, TXC, RXC>
+where
+    TXC: dma::Channel,
+    RXC: dma::Channel,
+    TxDma<I2C>: dma::Target,
+    RxDma<I2C>: dma::Target,
+{
+    i2c: I2c<I2C, PINS>,
+    tx: dma::Transfer<TXB, TXC, TxDma<I2C>>,
+    rx: dma::Transfer<RXB, RXC, RxDma<I2C>>,
+    addr: u8,
+    rx_len: usize,
+    /// Bytes of the current part after its current chunk
+    remaining: usize,
+    reading: bool,
+}
+
+/// Address of the slave, to which a transfer is started
+#[derive(Clone, Copy)]
+enum SlaveAddress {
+    /// 7 bit address
+    SevenBit(u8),
+    /// 10 bit address
+    TenBit(u16),
+    /// 10 bit address, of which only the header is sent, for a read after
+    /// a write to the same slave
+    TenBitHeader(u16),
+}
+
+impl SlaveAddress {
+    /// Returns SADD, ADD10 and HEAD10R
+    fn bits(self) -> (u16, bool, bool) {
+        match self {
+            SlaveAddress::SevenBit(addr) => (u16::from(addr) << 1, false, false),
+            SlaveAddress::TenBit(addr) => (addr, true, false),
+            SlaveAddress::TenBitHeader(addr) => (addr, true, true),
+        }
+    }
+}
+
+/// Own address of an I2C slave
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OwnAddress {
+    /// 7 bit address
+    SevenBit(u8),
+    /// 10 bit address
+    TenBit(u16),
+}
+
+/// Direction of a transfer, seen from the master
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    /// The master writes to the slave
+    Write,
+    /// The master reads from the slave
+    Read,
+}
+
+/// Events of an I2C slave
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SlaveEvent {
+    /// The slave has been addressed by a (repeated) start
+    AddressMatched {
+        /// The matched 7 bit address, or the two MSBs of a 10 bit address
+        /// in the header `0b11110xx`
+        address: u8,
+        /// Direction of the transfer
+        dir: Direction,
+    },
+    /// The master has written a byte
+    ByteReceived(u8),
+    /// The master reads the next byte, which has to be provided with
+    /// `I2cSlave::send`
+    ///
+    /// The clock is stretched until then.
+    NeedData,
+    /// The master has ended the transfer with a stop
+    Stop,
+}
+
+/// I2C peripheral operating in slave mode
+///
+/// The clock is stretched until an event is handled, so
+/// [`poll`](#method.poll) can be called from the interrupt handlers of the I2C.
+pub struct I2cSlave<I2C, PINS> {
+    i2c: I2C,
+    pins: PINS,
+}
+
+/// A transaction of the interrupt driven I2C master
+pub enum Transaction {
+    /// Write the bytes to the slave
+    Write {
+        /// 7 bit address of the slave
+        addr: u8,
+        /// The bytes to send
+        bytes: &'static [u8],
+    },
+    /// Fill the buffer with bytes read from the slave
+    Read {
+        /// 7 bit address of the slave
+        addr: u8,
+        /// The buffer to fill
+        buffer: &'static mut [u8],
+    },
+    /// Write the bytes to the slave and fill the buffer with bytes read from
+    /// it after a repeated start
+    WriteRead {
+        /// 7 bit address of the slave
+        addr: u8,
+        /// The bytes to send
+        bytes: &'static [u8],
+        /// The buffer to fill
+        buffer: &'static mut [u8],
+    },
+}
+
+/// Interrupt driven I2C master
+///
+/// A transaction is started with [`start`](#method.start) and driven by
+/// [`on_event`](#method.on_event) and [`on_error`](#method.on_error), which
+/// have to be called by the event and error interrupt handlers of the I2C.
+/// Its result is returned by [`poll`](#method.poll).
+pub struct InterruptI2c<I2C, PINS> {
+    i2c: I2c<I2C, PINS>,
+    transaction: Option<Transaction>,
+    /// The read part of the transaction is in progress
+    reading: bool,
+    /// Position in the buffer of the current part
+    index: usize,
+    /// Bytes of the current part, which are not yet covered by NBYTES
+    remaining: usize,
+    nack: bool,
+    /// A PEC error or an overrun, which is reported once the transaction ends
+    error: Option<Error>,
+    /// An SMBus alert has been signaled
+    alert: bool,
+    result: Option<Result<(), Error>>,
+}
+
+/// Returns TIMEOUTA for the timeout
+///
+/// # Panics
+///
+/// Panics if the timeout is zero or too long for the I2C clock.
+fn timeouta(timeout: MilliSeconds, clocks: Clocks) -> u16 {
+    // t_TIMEOUT = (TIMEOUTA + 1) * 2048 * t_I2CCLK
+    let cycles = u64::from(timeout.0) * u64::from(clocks.pclk1().0) / 1_000;
+    let timeouta = (cycles + 2047) / 2048;
+    assert!(timeouta > 0 && timeouta <= 0x1000);
+    (timeouta - 1) as u16
+}
+
+/// Configures the SCL low timeout in TIMEOUTR, or disables it with `None`
+///
+/// # Panics
+///
+/// Panics if the timeout is zero or too long for the I2C clock.
+fn set_timeout(i2c: &i2c1::RegisterBlock, timeout: Option<MilliSeconds>, clocks: Clocks) {
+    // TIMEOUTA can only be written while TIMOUTEN is cleared
+    i2c.timeoutr.modify(|_, w| w.timouten().clear_bit());
+    if let Some(timeout) = timeout {
+        let timeouta = timeouta(timeout, clocks);
+        // NOTE(unsafe) the value fits into TIMEOUTA
+        i2c.timeoutr
+            .modify(|_, w| unsafe { w.timeouta().bits(timeouta) }.tidle().clear_bit());
+        i2c.timeoutr.modify(|_, w| w.timouten().set_bit());
+    }
+}
+
+/// Sets or clears an FMP bit of SYSCFG_CFGR1
+fn set_fast_mode_plus(syscfg: &mut SysCfg, fmp: u8, enable: bool) {
+    let bit = 1 << fmp;
+    // NOTE(unsafe) only the FMP bit is changed
+    syscfg.cfgr1().modify(|r, w| unsafe {
+        w.bits(if enable {
+            r.bits() | bit
+        } else {
+            r.bits() & !bit
+        })
+    });
+}
+
 macro_rules! busy_wait {
     ($i2c:expr, $flag:ident, $variant:ident) => {
         loop {
@@ -91,6 +557,20 @@ macro_rules! busy_wait {
                 return Err(Error::Bus);
             } else if isr.arlo().is_lost() {
                 return Err(Error::Arbitration);
+            } else if isr.timeout().bit_is_set() {
+                $i2c.icr.write(|w| w.timoutcf().set_bit());
+                return Err(Error::Timeout);
+            } else if isr.pecerr().bit_is_set() {
+                $i2c.icr.write(|w| w.peccf().set_bit());
+                return Err(Error::Pec);
+            } else if isr.nackf().bit_is_set() {
+                // Without AUTOEND, the STOP has to be generated by software
+                if $i2c.cr2.read().autoend().bit_is_clear() {
+                    $i2c.cr2.modify(|_, w| w.stop().set_bit());
+                }
+                while $i2c.isr.read().stopf().bit_is_clear() {}
+                $i2c.icr.write(|w| w.nackcf().set_bit().stopcf().set_bit());
+                return Err(Error::Nack);
             } else {
                 // try again
             }
@@ -99,18 +579,21 @@ macro_rules! busy_wait {
 }
 
 macro_rules! hal {
-    ($($I2CX:ident: ($i2cX:ident, $i2cXen:ident, $i2cXrst:ident),)+) => {
+    ($($I2CX:ident: ($i2cX:ident, $i2cXen:ident, $i2cXrst:ident, $fmp:expr),)+) => {
         $(
             impl<SCL, SDA> I2c<$I2CX, (SCL, SDA)> {
                 /// Configures the I2C peripheral to work in master mode
-                pub fn $i2cX<F>(
+                ///
+                /// The timing is either a bus speed or a `TimingConfig`. Timings,
+                /// which can not be met with the I2C clock, are rejected.
+                pub fn $i2cX<T>(
                     i2c: $I2CX,
                     pins: (SCL, SDA),
-                    freq: F,
+                    timing: T,
                     clocks: Clocks,
                     apb1: &mut APB1,
-                ) -> Self where
-                    F: Into<Hertz>,
+                ) -> Result<Self, TimingError> where
+                    T: Into<TimingConfig>,
                     SCL: SclPin<$I2CX>,
                     SDA: SdaPin<$I2CX>,
                 {
@@ -118,69 +601,35 @@ macro_rules! hal {
                     apb1.rstr().modify(|_, w| w.$i2cXrst().reset());
                     apb1.rstr().modify(|_, w| w.$i2cXrst().clear_bit());
 
-                    let freq = freq.into().0;
-
-                    assert!(freq <= 1_000_000);
-
-                    // TODO review compliance with the timing requirements of I2C
-                    // t_I2CCLK = 1 / PCLK1
-                    // t_PRESC  = (PRESC + 1) * t_I2CCLK
-                    // t_SCLL   = (SCLL + 1) * t_PRESC
-                    // t_SCLH   = (SCLH + 1) * t_PRESC
-                    //
-                    // t_SYNC1 + t_SYNC2 > 4 * t_I2CCLK
-                    // t_SCL ~= t_SYNC1 + t_SYNC2 + t_SCLL + t_SCLH
-                    let i2cclk = clocks.pclk1().0;
-                    let ratio = i2cclk / freq - 4;
-                    let (presc, scll, sclh, sdadel, scldel) = if freq >= 100_000 {
-                        // fast-mode or fast-mode plus
-                        // here we pick SCLL + 1 = 2 * (SCLH + 1)
-                        let presc = ratio / 387;
-
-                        let sclh = ((ratio / (presc + 1)) - 3) / 3;
-                        let scll = 2 * (sclh + 1) - 1;
-
-                        let (sdadel, scldel) = if freq > 400_000 {
-                            // fast-mode plus
-                            let sdadel = 0;
-                            let scldel = i2cclk / 4_000_000 / (presc + 1) - 1;
+                    let mut i2c = I2c { i2c, pins };
+                    i2c.set_timing(timing.into(), clocks)?;
 
-                            (sdadel, scldel)
-                        } else {
-                            // fast-mode
-                            let sdadel = i2cclk / 8_000_000 / (presc + 1);
-                            let scldel = i2cclk / 2_000_000 / (presc + 1) - 1;
-
-                            (sdadel, scldel)
-                        };
-
-                        (presc, scll, sclh, sdadel, scldel)
-                    } else {
-                        // standard-mode
-                        // here we pick SCLL = SCLH
-                        let presc = ratio / 514;
+                    Ok(i2c)
+                }
 
-                        let sclh = ((ratio / (presc + 1)) - 2) / 2;
-                        let scll = sclh;
+                /// Releases the I2C peripheral and associated pins
+                pub fn free(self) -> ($I2CX, (SCL, SDA)) {
+                    (self.i2c, self.pins)
+                }
+            }
 
-                        let sdadel = i2cclk / 2_000_000 / (presc + 1);
-                        let scldel = i2cclk / 800_000 / (presc + 1) - 1;
+            impl<PINS> I2c<$I2CX, PINS> {
+                /// Configures the timing and the noise filters
+                ///
+                /// The peripheral is disabled meanwhile, so this must not be
+                /// called during a transfer.
+                pub fn set_timing(
+                    &mut self,
+                    timing: TimingConfig,
+                    clocks: Clocks,
+                ) -> Result<(), TimingError> {
+                    let (presc, scll, sclh, sdadel, scldel) = timing.timingr(clocks.pclk1().0)?;
 
-                        (presc, scll, sclh, sdadel, scldel)
-                    };
+                    // TIMINGR, ANFOFF and DNF can only be written while PE is cleared
+                    self.i2c.cr1.modify(|_, w| w.pe().clear_bit());
 
-                    let presc = u8(presc).unwrap();
-                    assert!(presc < 16);
-                    let scldel = u8(scldel).unwrap();
-                    assert!(scldel < 16);
-                    let sdadel = u8(sdadel).unwrap();
-                    assert!(sdadel < 16);
-                    let sclh = u8(sclh).unwrap();
-                    let scll = u8(scll).unwrap();
-
-                    // Configure for "fast mode" (400 KHz)
                     // NOTE(write): writes all non-reserved bits.
-                    i2c.timingr.write(|w| {
+                    self.i2c.timingr.write(|w| {
                         w.presc()
                             .bits(presc)
                             .scll()
@@ -193,10 +642,620 @@ macro_rules! hal {
                             .bits(scldel)
                     });
 
+                    // ANFOFF and DNF
+                    let filters = (u32::from(!timing.analog_filter) << 12)
+                        | (u32::from(timing.digital_filter) << 8);
+                    // NOTE(unsafe) only the filter bits are changed
+                    self.i2c
+                        .cr1
+                        .modify(|r, w| unsafe { w.bits((r.bits() & !(0x1F << 8)) | filters) });
+
                     // Enable the peripheral
+                    self.i2c.cr1.modify(|_, w| w.pe().set_bit());
+
+                    Ok(())
+                }
+
+                /// Enables or disables the Fast-mode Plus drive of the I2C pins
+                ///
+                /// The high current drive is required for speeds above 400 kHz.
+                /// PB6 to PB9 have their own drive, which is set with
+                /// [`FastModePlusPin`](trait.FastModePlusPin.html).
+                pub fn set_fast_mode_plus(&mut self, enable: bool, syscfg: &mut SysCfg) {
+                    // I2Cx_FMP
+                    set_fast_mode_plus(syscfg, $fmp, enable);
+                }
+            }
+
+            impl dma::Target for RxDma<$I2CX> {
+                fn enable_dma(&mut self) {
+                    // NOTE(unsafe) critical section prevents races
+                    interrupt::free(|_| unsafe {
+                        let cr1 = &(*$I2CX::ptr()).cr1;
+                        cr1.modify(|_, w| w.rxdmaen().set_bit());
+                    });
+                }
+
+                fn disable_dma(&mut self) {
+                    // NOTE(unsafe) critical section prevents races
+                    interrupt::free(|_| unsafe {
+                        let cr1 = &(*$I2CX::ptr()).cr1;
+                        cr1.modify(|_, w| w.rxdmaen().clear_bit());
+                    });
+                }
+            }
+
+            impl dma::Target for TxDma<$I2CX> {
+                fn enable_dma(&mut self) {
+                    // NOTE(unsafe) critical section prevents races
+                    interrupt::free(|_| unsafe {
+                        let cr1 = &(*$I2CX::ptr()).cr1;
+                        cr1.modify(|_, w| w.txdmaen().set_bit());
+                    });
+                }
+
+                fn disable_dma(&mut self) {
+                    // NOTE(unsafe) critical section prevents races
+                    interrupt::free(|_| unsafe {
+                        let cr1 = &(*$I2CX::ptr()).cr1;
+                        cr1.modify(|_, w| w.txdmaen().clear_bit());
+                    });
+                }
+            }
+
+            impl<PINS> I2c<$I2CX, PINS> {
+                /// Write the buffer to the slave using DMA
+                ///
+                /// # Panics
+                ///
+                /// Panics if the buffer is empty.
+                pub fn write_dma<B, C>(
+                    mut self,
+                    addr: u8,
+                    buffer: B,
+                    mut channel: C,
+                ) -> DmaTransfer<$I2CX, PINS, B, C, TxDma<$I2CX>>
+                where
+                    B: dma::ReadBuffer<Word = u8> + 'static,
+                    C: dma::Channel,
+                    TxDma<$I2CX>: dma::OnChannel<C>,
+                {
+                    // NOTE(unsafe) only the length of the buffer is used here
+                    let (_, len) = unsafe { buffer.read_buffer() };
+                    assert!(len > 0);
+
+                    let pa = &self.i2c.txdr as *const _ as u32;
+                    channel.set_peripheral_address(pa, dma::Increment::Disable);
+                    let transfer = dma::Transfer::start_read(buffer, channel, TxDma { _i2c: PhantomData });
+
+                    // START and prepare to send `len` bytes
+                    self.start_chunks(SlaveAddress::SevenBit(addr), false, len, true, false);
+
+                    DmaTransfer {
+                        i2c: self,
+                        transfer,
+                        remaining: len.saturating_sub(255),
+                    }
+                }
+
+                /// Fill the buffer with bytes read from the slave using DMA
+                ///
+                /// # Panics
+                ///
+                /// Panics if the buffer is empty.
+                pub fn read_dma<B, C>(
+                    mut self,
+                    addr: u8,
+                    mut buffer: B,
+                    mut channel: C,
+                ) -> DmaTransfer<$I2CX, PINS, B, C, RxDma<$I2CX>>
+                where
+                    B: dma::WriteBuffer<Word = u8> + 'static,
+                    C: dma::Channel,
+                    RxDma<$I2CX>: dma::OnChannel<C>,
+                {
+                    // NOTE(unsafe) only the length of the buffer is used here
+                    let (_, len) = unsafe { buffer.write_buffer() };
+                    assert!(len > 0);
+
+                    let pa = &self.i2c.rxdr as *const _ as u32;
+                    channel.set_peripheral_address(pa, dma::Increment::Disable);
+                    let transfer = dma::Transfer::start_write(buffer, channel, RxDma { _i2c: PhantomData });
+
+                    // START and prepare to receive `len` bytes
+                    self.start_chunks(SlaveAddress::SevenBit(addr), true, len, true, false);
+
+                    DmaTransfer {
+                        i2c: self,
+                        transfer,
+                        remaining: len.saturating_sub(255),
+                    }
+                }
+
+                /// Write the TX buffer to the slave and fill the RX buffer with
+                /// bytes read from it after a repeated start using DMA
+                ///
+                /// # Panics
+                ///
+                /// Panics if a buffer is empty.
+                pub fn write_read_dma<TXB, RXB, TXC, RXC>(
+                    mut self,
+                    addr: u8,
+                    tx_buffer: TXB,
+                    mut rx_buffer: RXB,
+                    mut tx_channel: TXC,
+                    mut rx_channel: RXC,
+                ) -> DmaWriteRead<$I2CX, PINS, TXB, RXB, TXC, RXC>
+                where
+                    TXB: dma::ReadBuffer<Word = u8> + 'static,
+                    RXB: dma::WriteBuffer<Word = u8> + 'static,
+                    TXC: dma::Channel,
+                    RXC: dma::Channel,
+                    TxDma<$I2CX>: dma::OnChannel<TXC>,
+                    RxDma<$I2CX>: dma::OnChannel<RXC>,
+                {
+                    // NOTE(unsafe) only the lengths of the buffers are used here
+                    let (_, tx_len) = unsafe { tx_buffer.read_buffer() };
+                    let (_, rx_len) = unsafe { rx_buffer.write_buffer() };
+                    assert!(tx_len > 0);
+                    assert!(rx_len > 0);
+
+                    let pa = &self.i2c.txdr as *const _ as u32;
+                    tx_channel.set_peripheral_address(pa, dma::Increment::Disable);
+                    let pa = &self.i2c.rxdr as *const _ as u32;
+                    rx_channel.set_peripheral_address(pa, dma::Increment::Disable);
+
+                    let rx = dma::Transfer::start_write(rx_buffer, rx_channel, RxDma { _i2c: PhantomData });
+                    let tx = dma::Transfer::start_read(tx_buffer, tx_channel, TxDma { _i2c: PhantomData });
+
+                    // START and prepare to send `tx_len` bytes
+                    self.start_chunks(SlaveAddress::SevenBit(addr), false, tx_len, false, false);
+
+                    DmaWriteRead {
+                        i2c: self,
+                        tx,
+                        rx,
+                        addr,
+                        rx_len,
+                        remaining: tx_len.saturating_sub(255),
+                        reading: false,
+                    }
+                }
+
+                /// Returns the error of the transfer, which has ended, and clears
+                /// the flags
+                fn end_transfer(&mut self) -> Result<(), Error> {
+                    let isr = self.i2c.isr.read();
+                    let result = if isr.berr().is_error() {
+                        Err(Error::Bus)
+                    } else if isr.arlo().is_lost() {
+                        Err(Error::Arbitration)
+                    } else if isr.timeout().bit_is_set() {
+                        Err(Error::Timeout)
+                    } else if isr.nackf().bit_is_set() {
+                        Err(Error::Nack)
+                    } else {
+                        Ok(())
+                    };
+
+                    if isr.nackf().bit_is_set() {
+                        // Without AUTOEND, the STOP has to be generated by software
+                        if self.i2c.cr2.read().autoend().bit_is_clear() {
+                            self.i2c.cr2.modify(|_, w| w.stop().set_bit());
+                        }
+                        while self.i2c.isr.read().stopf().bit_is_clear() {}
+                    }
+
+                    // Flush TXDR, which might have been filled by the DMA
+                    self.i2c.isr.write(|w| w.txe().set_bit());
+                    self.i2c.icr.write(|w| {
+                        w.stopcf()
+                            .set_bit()
+                            .nackcf()
+                            .set_bit()
+                            .berrcf()
+                            .set_bit()
+                            .arlocf()
+                            .set_bit()
+                            .timoutcf()
+                            .set_bit()
+                    });
+
+                    result
+                }
+            }
+
+            impl<PINS, B, C, T> DmaTransfer<$I2CX, PINS, B, C, T>
+            where
+                C: dma::Channel,
+                T: dma::Target,
+            {
+                /// Has this transfer ended, successfully or with an error?
+                ///
+                /// Transfers of more than 255 bytes are continued with the next
+                /// chunk here, so this has to be called repeatedly, e.g. on the
+                /// transfer complete interrupt of the I2C.
+                pub fn is_complete(&mut self) -> bool {
+                    let isr = self.i2c.i2c.isr.read();
+                    if isr.tcr().bit_is_set() {
+                        self.i2c.reload_next_chunk(&mut self.remaining);
+                    }
+                    isr.stopf().bit_is_set()
+                        || isr.nackf().bit_is_set()
+                        || isr.berr().is_error()
+                        || isr.arlo().is_lost()
+                        || isr.timeout().bit_is_set()
+                }
+
+                /// Block until this transfer has ended and return its result and
+                /// ownership over its parts
+                pub fn wait(mut self) -> (Result<(), Error>, B, C, I2c<$I2CX, PINS>) {
+                    while !self.is_complete() {}
+
+                    let (buffer, channel, _) = self.transfer.stop();
+                    let mut i2c = self.i2c;
+                    let result = i2c.end_transfer();
+
+                    (result, buffer, channel, i2c)
+                }
+            }
+
+            impl<PINS, TXB, RXB, TXC, RXC> DmaWriteRead<$I2CX, PINS, TXB, RXB, TXC, RXC>
+            where
+                TXC: dma::Channel,
+                RXC: dma::Channel,
+            {
+                /// Drives the transfer and returns, whether it has ended
+                ///
+                /// This has to be called repeatedly, e.g. on the transfer
+                /// complete interrupt of the I2C, to issue the repeated start
+                /// after the write. Errors are only reported by
+                /// [`wait`](#method.wait), after this returns `Ok` or an error.
+                pub fn poll(&mut self) -> nb::Result<(), Error> {
+                    let isr = self.i2c.i2c.isr.read();
+                    if isr.berr().is_error() {
+                        Err(nb::Error::Other(Error::Bus))
+                    } else if isr.arlo().is_lost() {
+                        Err(nb::Error::Other(Error::Arbitration))
+                    } else if isr.timeout().bit_is_set() {
+                        Err(nb::Error::Other(Error::Timeout))
+                    } else if isr.nackf().bit_is_set() {
+                        Err(nb::Error::Other(Error::Nack))
+                    } else if isr.tcr().bit_is_set() {
+                        self.i2c.reload_next_chunk(&mut self.remaining);
+                        Err(nb::Error::WouldBlock)
+                    } else if !self.reading && isr.tc().is_complete() {
+                        // reSTART and prepare to receive `rx_len` bytes
+                        let addr = SlaveAddress::SevenBit(self.addr);
+                        self.i2c.start_chunks(addr, true, self.rx_len, true, false);
+                        self.remaining = self.rx_len.saturating_sub(255);
+                        self.reading = true;
+                        Err(nb::Error::WouldBlock)
+                    } else if self.reading && isr.stopf().bit_is_set() {
+                        Ok(())
+                    } else {
+                        Err(nb::Error::WouldBlock)
+                    }
+                }
+
+                /// Block until this transfer has ended and return its result and
+                /// ownership over its parts
+                ///
+                /// Returns the result, the TX buffer, the RX buffer, the TX channel,
+                /// the RX channel and the I2C.
+                pub fn wait(mut self) -> (Result<(), Error>, TXB, RXB, TXC, RXC, I2c<$I2CX, PINS>) {
+                    while let Err(nb::Error::WouldBlock) = self.poll() {}
+
+                    let (rx_buffer, rx_channel, _) = self.rx.stop();
+                    let (tx_buffer, tx_channel, _) = self.tx.stop();
+                    let mut i2c = self.i2c;
+                    let result = i2c.end_transfer();
+
+                    (result, tx_buffer, rx_buffer, tx_channel, rx_channel, i2c)
+                }
+            }
+
+            impl<PINS> I2c<$I2CX, PINS> {
+                /// Converts the I2C into an interrupt driven master
+                ///
+                /// Enables the event and error interrupts of the I2C.
+                pub fn into_interrupt_driven(self) -> InterruptI2c<$I2CX, PINS> {
+                    self.i2c.cr1.modify(|_, w| {
+                        w.txie()
+                            .set_bit()
+                            .rxie()
+                            .set_bit()
+                            .nackie()
+                            .set_bit()
+                            .stopie()
+                            .set_bit()
+                            .tcie()
+                            .set_bit()
+                            .errie()
+                            .set_bit()
+                    });
+
+                    InterruptI2c {
+                        i2c: self,
+                        transaction: None,
+                        reading: false,
+                        index: 0,
+                        remaining: 0,
+                        nack: false,
+                        error: None,
+                        alert: false,
+                        result: None,
+                    }
+                }
+            }
+
+            impl<PINS> InterruptI2c<$I2CX, PINS> {
+                /// Starts a transaction
+                ///
+                /// Returns the transaction, if another one is in progress or its
+                /// result has not been taken with [`poll`](#method.poll).
+                ///
+                /// # Panics
+                ///
+                /// Panics if a buffer of the transaction is empty.
+                pub fn start(&mut self, transaction: Transaction) -> Result<(), Transaction> {
+                    if self.transaction.is_some() {
+                        return Err(transaction);
+                    }
+
+                    let len = match &transaction {
+                        Transaction::Write { bytes, .. } => bytes.len(),
+                        Transaction::Read { buffer, .. } => buffer.len(),
+                        Transaction::WriteRead { bytes, buffer, .. } => {
+                            assert!(!buffer.is_empty());
+                            bytes.len()
+                        }
+                    };
+                    assert!(len > 0);
+
+                    self.reading = matches!(transaction, Transaction::Read { .. });
+                    self.transaction = Some(transaction);
+                    self.nack = false;
+                    self.error = None;
+                    self.start_part(len);
+
+                    Ok(())
+                }
+
+                /// Returns true, if a transaction is in progress
+                pub fn is_busy(&self) -> bool {
+                    self.transaction.is_some() && self.result.is_none()
+                }
+
+                /// Returns the transaction, when it has ended
+                ///
+                /// The error is returned together with the transaction, so that
+                /// its buffers can be reused.
+                pub fn poll(&mut self) -> nb::Result<Transaction, (Error, Transaction)> {
+                    match self.result.take() {
+                        None => Err(nb::Error::WouldBlock),
+                        Some(result) => {
+                            let transaction = self.transaction.take().unwrap();
+                            match result {
+                                Ok(()) => Ok(transaction),
+                                Err(error) => Err(nb::Error::Other((error, transaction))),
+                            }
+                        }
+                    }
+                }
+
+                /// Advances the transaction, has to be called by the event
+                /// interrupt handler of the I2C
+                pub fn on_event(&mut self) {
+                    let i2c = &self.i2c.i2c;
+                    let isr = i2c.isr.read();
+
+                    if isr.txis().bit_is_set() {
+                        match &self.transaction {
+                            Some(Transaction::Write { bytes, .. })
+                            | Some(Transaction::WriteRead { bytes, .. }) => {
+                                // NOTE(write): writes all non-reserved bits.
+                                i2c.txdr.write(|w| w.txdata().bits(bytes[self.index]));
+                                self.index += 1;
+                            }
+                            _ => {
+                                // A transaction in progress never leaves TXIS set
+                            }
+                        }
+                    }
+
+                    if isr.rxne().bit_is_set() {
+                        // Reading RXDR clears RXNE
+                        let byte = i2c.rxdr.read().rxdata().bits();
+                        match &mut self.transaction {
+                            Some(Transaction::Read { buffer, .. })
+                            | Some(Transaction::WriteRead { buffer, .. }) => {
+                                buffer[self.index] = byte;
+                                self.index += 1;
+                            }
+                            _ => {}
+                        }
+                    }
+
+                    if isr.tcr().bit_is_set() {
+                        // The next chunk of the current part
+                        self.i2c.reload_next_chunk(&mut self.remaining);
+                    }
+
+                    if isr.nackf().bit_is_set() {
+                        i2c.icr.write(|w| w.nackcf().set_bit());
+                        self.nack = true;
+                        // Without AUTOEND, the STOP has to be generated by software
+                        if i2c.cr2.read().autoend().bit_is_clear() {
+                            i2c.cr2.modify(|_, w| w.stop().set_bit());
+                        }
+                    } else if isr.tc().bit_is_set() && !self.reading {
+                        // The write part of a WriteRead is done
+                        let len = match &self.transaction {
+                            Some(Transaction::WriteRead { buffer, .. }) => buffer.len(),
+                            _ => 0,
+                        };
+                        self.reading = true;
+                        self.start_part(len);
+                    }
+
+                    if isr.stopf().bit_is_set() {
+                        let i2c = &self.i2c.i2c;
+                        i2c.icr.write(|w| w.stopcf().set_bit());
+                        // Flush TXDR
+                        i2c.isr.write(|w| w.txe().set_bit());
+                        if self.transaction.is_some() && self.result.is_none() {
+                            self.result = Some(match self.error.take() {
+                                Some(error) => Err(error),
+                                None if self.nack => Err(Error::Nack),
+                                None => Ok(()),
+                            });
+                        }
+                    }
+                }
+
+                /// Handles bus errors and arbitration loss, has to be called by the
+                /// error interrupt handler of the I2C
+                ///
+                /// On bus errors, arbitration loss and timeouts the transaction is
+                /// aborted and the I2C is reset. PEC errors and overruns are
+                /// reported, when the transaction ends, SMBus alerts are
+                /// recorded for [`take_alert`](#method.take_alert). Every flag,
+                /// which triggers the error interrupt, is cleared.
+                pub fn on_error(&mut self) {
+                    let i2c = &self.i2c.i2c;
+                    let isr = i2c.isr.read();
+
+                    if isr.alert().bit_is_set() {
+                        i2c.icr.write(|w| w.alertcf().set_bit());
+                        self.alert = true;
+                    }
+
+                    let error = if isr.berr().is_error() {
+                        Error::Bus
+                    } else if isr.arlo().is_lost() {
+                        Error::Arbitration
+                    } else if isr.timeout().bit_is_set() {
+                        Error::Timeout
+                    } else {
+                        // These errors do not disturb the bus, they are reported
+                        // once the transaction ends with a STOP
+                        if isr.pecerr().bit_is_set() {
+                            i2c.icr.write(|w| w.peccf().set_bit());
+                            self.error = Some(Error::Pec);
+                        }
+                        if isr.ovr().bit_is_set() {
+                            i2c.icr.write(|w| w.ovrcf().set_bit());
+                            self.error = Some(Error::Overrun);
+                        }
+                        return;
+                    };
+
+                    // Software reset: PE has to be low for 3 APB clock cycles,
+                    // reading it back ensures this.
+                    i2c.cr1.modify(|_, w| w.pe().clear_bit());
+                    while i2c.cr1.read().pe().bit_is_set() {}
                     i2c.cr1.modify(|_, w| w.pe().set_bit());
 
-                    I2c { i2c, pins }
+                    if self.transaction.is_some() && self.result.is_none() {
+                        self.result = Some(Err(error));
+                    }
+                }
+
+                /// Returns true, if an SMBus alert has been signaled since the
+                /// last call
+                pub fn take_alert(&mut self) -> bool {
+                    core::mem::replace(&mut self.alert, false)
+                }
+
+                /// Disables the interrupts and returns the I2C
+                ///
+                /// A transaction in progress is not aborted.
+                pub fn free(self) -> I2c<$I2CX, PINS> {
+                    self.i2c.i2c.cr1.modify(|_, w| {
+                        w.txie()
+                            .clear_bit()
+                            .rxie()
+                            .clear_bit()
+                            .nackie()
+                            .clear_bit()
+                            .stopie()
+                            .clear_bit()
+                            .tcie()
+                            .clear_bit()
+                            .errie()
+                            .clear_bit()
+                    });
+                    self.i2c
+                }
+
+                /// Starts the write or read part of the transaction with `len` bytes
+                fn start_part(&mut self, len: usize) {
+                    let (addr, last) = match &self.transaction {
+                        Some(Transaction::Write { addr, .. }) => (*addr, true),
+                        Some(Transaction::Read { addr, .. }) => (*addr, true),
+                        Some(Transaction::WriteRead { addr, .. }) => (*addr, self.reading),
+                        None => return,
+                    };
+                    self.index = 0;
+                    self.remaining = len - cmp::min(len, 255);
+
+                    // (re)START, the STOP is generated automatically after the last part
+                    let addr = SlaveAddress::SevenBit(addr);
+                    self.i2c.start_chunks(addr, self.reading, len, last, false);
+                }
+            }
+
+            impl<SCL, SDA> I2cSlave<$I2CX, (SCL, SDA)> {
+                /// Configures the I2C peripheral to work in slave mode with the
+                /// given own address
+                ///
+                /// # Panics
+                ///
+                /// Panics if the address does not fit into 7 or 10 bits.
+                pub fn $i2cX(
+                    i2c: $I2CX,
+                    pins: (SCL, SDA),
+                    address: OwnAddress,
+                    clocks: Clocks,
+                    apb1: &mut APB1,
+                ) -> Self
+                where
+                    SCL: SclPin<$I2CX>,
+                    SDA: SdaPin<$I2CX>,
+                {
+                    apb1.enr().modify(|_, w| w.$i2cXen().enabled());
+                    apb1.rstr().modify(|_, w| w.$i2cXrst().reset());
+                    apb1.rstr().modify(|_, w| w.$i2cXrst().clear_bit());
+
+                    // Only the data hold and setup times are used by a slave.
+                    // With t_PRESC >= 125 ns, SDADEL = 1 and SCLDEL = 3 fulfill
+                    // them for all speeds up to fast-mode.
+                    let i2cclk = clocks.pclk1().0;
+                    let presc = u8((i2cclk + 7_999_999) / 8_000_000 - 1).unwrap();
+                    assert!(presc < 16);
+                    // NOTE(write): writes all non-reserved bits.
+                    i2c.timingr.write(|w| w.presc().bits(presc).sdadel().bits(1).scldel().bits(3));
+
+                    // OA1 with OA1MODE
+                    let oar1 = match address {
+                        OwnAddress::SevenBit(addr) => {
+                            assert!(addr < 0x80);
+                            u32::from(addr) << 1
+                        }
+                        OwnAddress::TenBit(addr) => {
+                            assert!(addr < 0x400);
+                            u32::from(addr) | (1 << 10)
+                        }
+                    };
+                    // NOTE(unsafe) the address fits into OA1
+                    i2c.oar1.write(|w| unsafe { w.bits(oar1) });
+                    i2c.oar1.modify(|_, w| w.oa1en().set_bit());
+
+                    // Enable the peripheral
+                    i2c.cr1.modify(|_, w| w.pe().set_bit());
+
+                    I2cSlave { i2c, pins }
                 }
 
                 /// Releases the I2C peripheral and associated pins
@@ -205,39 +1264,163 @@ macro_rules! hal {
                 }
             }
 
-            impl<PINS> Read for I2c<$I2CX, PINS> {
-                type Error = Error;
-                fn read(&mut self, addr: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
-                    // TODO support transfers of more than 255 bytes
-                    assert!(buffer.len() < 256 && buffer.len() > 0);
+            impl<PINS> I2cSlave<$I2CX, PINS> {
+                /// Sets a second 7 bit address, of which the `mask` lowest bits
+                /// are ignored, or disables it with `None`
+                ///
+                /// With a mask of 7, all addresses except the reserved ones match.
+                ///
+                /// # Panics
+                ///
+                /// Panics if the address does not fit into 7 bits or the mask is
+                /// greater than 7.
+                pub fn set_second_address(&mut self, address: Option<(u8, u8)>) {
+                    // OA2EN has to be cleared before OA2 is written
+                    self.i2c.oar2.write(|w| w.oa2en().clear_bit());
+                    if let Some((addr, mask)) = address {
+                        assert!(addr < 0x80);
+                        assert!(mask <= 7);
+                        // OA2 and OA2MSK
+                        let bits = (u32::from(addr) << 1) | (u32::from(mask) << 8);
+                        // NOTE(unsafe) the address and the mask fit into their fields
+                        self.i2c.oar2.write(|w| unsafe { w.bits(bits) });
+                        self.i2c.oar2.modify(|_, w| w.oa2en().set_bit());
+                    }
+                }
 
-                    // TODO do we have to explicitly wait here if the bus is busy (e.g. another
-                    // master is communicating)?
+                /// Enables the interrupts of all slave events and errors
+                pub fn listen(&mut self) {
+                    self.i2c.cr1.modify(|_, w| {
+                        w.addrie()
+                            .set_bit()
+                            .rxie()
+                            .set_bit()
+                            .txie()
+                            .set_bit()
+                            .stopie()
+                            .set_bit()
+                            .nackie()
+                            .set_bit()
+                            .errie()
+                            .set_bit()
+                    });
+                }
 
-                    // START and prepare to receive `bytes`
-                    self.i2c.cr2.write(|w| {
-                        w.sadd()
-                            .bits(u16::from(addr << 1))
-                            .rd_wrn()
-                            .read()
-                            .nbytes()
-                            .bits(buffer.len() as u8)
-                            .start()
-                            .start()
-                            .autoend()
-                            .automatic()
+                /// Disables the interrupts of all slave events and errors
+                pub fn unlisten(&mut self) {
+                    self.i2c.cr1.modify(|_, w| {
+                        w.addrie()
+                            .clear_bit()
+                            .rxie()
+                            .clear_bit()
+                            .txie()
+                            .clear_bit()
+                            .stopie()
+                            .clear_bit()
+                            .nackie()
+                            .clear_bit()
+                            .errie()
+                            .clear_bit()
                     });
+                }
 
-                    for byte in buffer {
-                        // Wait until we have received something
-                        busy_wait!(self.i2c, rxne, is_not_empty);
+                /// Returns the next event
+                ///
+                /// Errors are returned as well, the transfer continues afterwards.
+                pub fn poll(&mut self) -> nb::Result<SlaveEvent, Error> {
+                    let isr = self.i2c.isr.read();
+
+                    if isr.berr().is_error() {
+                        self.i2c.icr.write(|w| w.berrcf().set_bit());
+                        Err(nb::Error::Other(Error::Bus))
+                    } else if isr.ovr().bit_is_set() {
+                        self.i2c.icr.write(|w| w.ovrcf().set_bit());
+                        Err(nb::Error::Other(Error::Overrun))
+                    } else if isr.timeout().bit_is_set() {
+                        self.i2c.icr.write(|w| w.timoutcf().set_bit());
+                        Err(nb::Error::Other(Error::Timeout))
 
-                        *byte = self.i2c.rxdr.read().rxdata().bits();
+                    } else if isr.rxne().bit_is_set() {
+                        // Reading RXDR clears RXNE
+                        Ok(SlaveEvent::ByteReceived(self.i2c.rxdr.read().rxdata().bits()))
+                    } else if isr.stopf().bit_is_set() {
+                        self.i2c.icr.write(|w| w.stopcf().set_bit());
+                        // Flush a byte, which has not been read by the master
+                        self.i2c.isr.write(|w| w.txe().set_bit());
+                        Ok(SlaveEvent::Stop)
+                    } else if isr.addr().bit_is_set() {
+                        let dir = if isr.dir().bit_is_set() {
+                            // Provide fresh data for the master
+                            self.i2c.isr.write(|w| w.txe().set_bit());
+                            Direction::Read
+                        } else {
+                            Direction::Write
+                        };
+                        let address = isr.addcode().bits();
+                        // Clearing ADDR releases the clock
+                        self.i2c.icr.write(|w| w.addrcf().set_bit());
+                        Ok(SlaveEvent::AddressMatched { address, dir })
+                    } else if isr.nackf().bit_is_set() {
+                        // The master does not read more bytes
+                        self.i2c.icr.write(|w| w.nackcf().set_bit());
+                        Err(nb::Error::WouldBlock)
+                    } else if isr.txis().bit_is_set() {
+                        Ok(SlaveEvent::NeedData)
+                    } else {
+                        Err(nb::Error::WouldBlock)
                     }
+                }
 
-                    // automatic STOP
+                /// Sends a byte to the master, after `SlaveEvent::NeedData`
+                pub fn send(&mut self, byte: u8) {
+                    // NOTE(write): writes all non-reserved bits.
+                    self.i2c.txdr.write(|w| w.txdata().bits(byte));
+                }
 
-                    Ok(())
+                /// Acknowledges the SMBus host address `0b000_1000` in addition
+                /// to the own addresses
+                pub fn set_smbus_host_address(&mut self, enable: bool) {
+                    self.i2c.cr1.modify(|_, w| w.smbhen().bit(enable));
+                }
+
+                /// Acknowledges the SMBus device default address `0b110_0001`
+                /// in addition to the own addresses
+                pub fn set_smbus_device_default_address(&mut self, enable: bool) {
+                    self.i2c.cr1.modify(|_, w| w.smbden().bit(enable));
+                }
+
+                /// Signals an SMBus alert to the host by driving the SMBA pin low
+                ///
+                /// The alert response address `0b000_1100` is acknowledged until
+                /// the alert is released.
+                pub fn assert_alert<SMBA>(&mut self, _pin: &SMBA)
+                where
+                    SMBA: SmbaPin<$I2CX>,
+                {
+                    self.i2c.cr1.modify(|_, w| w.alerten().set_bit());
+                }
+
+                /// Releases the SMBA pin
+                pub fn release_alert(&mut self) {
+                    self.i2c.cr1.modify(|_, w| w.alerten().clear_bit());
+                }
+
+                /// Sets the timeout, after which a clock held low by any device
+                /// is reported as `Error::Timeout`, or disables it with `None`
+                ///
+                /// # Panics
+                ///
+                /// Panics if the timeout can not be configured with the current
+                /// clocks.
+                pub fn set_timeout(&mut self, timeout: Option<MilliSeconds>, clocks: Clocks) {
+                    set_timeout(&self.i2c, timeout, clocks);
+                }
+            }
+
+            impl<PINS> Read for I2c<$I2CX, PINS> {
+                type Error = Error;
+                fn read(&mut self, addr: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+                    self.read_from(SlaveAddress::SevenBit(addr), buffer)
                 }
             }
 
@@ -245,32 +1428,133 @@ macro_rules! hal {
                 type Error = Error;
 
                 fn write(&mut self, addr: u8, bytes: &[u8]) -> Result<(), Error> {
-                    // TODO support transfers of more than 255 bytes
-                    assert!(bytes.len() < 256 && bytes.len() > 0);
+                    self.write_to(SlaveAddress::SevenBit(addr), bytes)
+                }
+            }
 
-                    // START and prepare to send `bytes`
-                    self.i2c.cr2.modify(|_, w| {
-                        w.sadd()
-                            .bits(u16::from(addr << 1))
-                            .rd_wrn()
-                            .write()
-                            .nbytes()
-                            .bits(bytes.len() as u8)
-                            .start()
-                            .start()
-                            .autoend()
-                            .automatic()
-                    });
+            impl<PINS> WriteRead for I2c<$I2CX, PINS> {
+                type Error = Error;
 
-                    for byte in bytes {
-                        // Wait until we are allowed to send data (START has been ACKed or last byte
-                        // when through)
-                        busy_wait!(self.i2c, txis, is_empty);
+                fn write_read(
+                    &mut self,
+                    addr: u8,
+                    bytes: &[u8],
+                    buffer: &mut [u8],
+                ) -> Result<(), Error> {
+                    self.write_read_with(SlaveAddress::SevenBit(addr), bytes, buffer)
+                }
+            }
 
-                        // put byte on the wire
-                        // NOTE(write): writes all non-reserved bits.
-                        self.i2c.txdr.write(|w| w.txdata().bits(*byte));
-                    }
+            impl<PINS> I2c<$I2CX, PINS> {
+                /// Reads enough bytes from the slave with a 10 bit address to
+                /// fill the buffer
+                ///
+                /// # Panics
+                ///
+                /// Panics if the address does not fit into 10 bits.
+                pub fn read_10bit(&mut self, addr: u16, buffer: &mut [u8]) -> Result<(), Error> {
+                    assert!(addr < 0x400);
+                    self.read_from(SlaveAddress::TenBit(addr), buffer)
+                }
+
+                /// Writes the bytes to the slave with a 10 bit address
+                ///
+                /// # Panics
+                ///
+                /// Panics if the address does not fit into 10 bits.
+                pub fn write_10bit(&mut self, addr: u16, bytes: &[u8]) -> Result<(), Error> {
+                    assert!(addr < 0x400);
+                    self.write_to(SlaveAddress::TenBit(addr), bytes)
+                }
+
+                /// Writes the bytes to the slave with a 10 bit address and reads
+                /// enough bytes from it after a repeated start to fill the buffer
+                ///
+                /// # Panics
+                ///
+                /// Panics if the address does not fit into 10 bits.
+                pub fn write_read_10bit(
+                    &mut self,
+                    addr: u16,
+                    bytes: &[u8],
+                    buffer: &mut [u8],
+                ) -> Result<(), Error> {
+                    assert!(addr < 0x400);
+                    self.write_read_with(SlaveAddress::TenBit(addr), bytes, buffer)
+                }
+
+                /// Enables or disables the SMBus packet error checking
+                ///
+                /// If enabled, the blocking transfers append the PEC byte to the
+                /// last write and check the PEC byte after the last read, a
+                /// mismatch is reported as `Error::Pec`. The DMA and interrupt
+                /// driven transfers do not use it.
+                pub fn set_pec(&mut self, enable: bool) {
+                    self.i2c.cr1.modify(|_, w| w.pecen().bit(enable));
+                }
+
+                /// Sets the timeout, after which a clock held low by any device
+                /// is reported as `Error::Timeout`, or disables it with `None`
+                ///
+                /// SMBus requires a timeout between 25 and 35 ms.
+                ///
+                /// # Panics
+                ///
+                /// Panics if the timeout can not be configured with the current
+                /// clocks.
+                pub fn set_timeout(&mut self, timeout: Option<MilliSeconds>, clocks: Clocks) {
+                    set_timeout(&self.i2c, timeout, clocks);
+                }
+
+                /// Enables the SMBus alert input on the SMBA pin
+                pub fn enable_alert<SMBA>(&mut self, _pin: &SMBA)
+                where
+                    SMBA: SmbaPin<$I2CX>,
+                {
+                    self.i2c.cr1.modify(|_, w| w.alerten().set_bit());
+                }
+
+                /// Disables the SMBus alert input
+                pub fn disable_alert(&mut self) {
+                    self.i2c.cr1.modify(|_, w| w.alerten().clear_bit());
+                }
+
+                /// Has a device signaled an alert on the SMBA pin?
+                ///
+                /// The alerting device is found by reading from the alert
+                /// response address `0b000_1100`.
+                pub fn is_alert_pending(&self) -> bool {
+                    self.i2c.isr.read().alert().bit_is_set()
+                }
+
+                /// Clears a pending alert
+                pub fn clear_alert(&mut self) {
+                    self.i2c.icr.write(|w| w.alertcf().set_bit());
+                }
+
+                fn read_from(&mut self, addr: SlaveAddress, buffer: &mut [u8]) -> Result<(), Error> {
+                    assert!(!buffer.is_empty());
+
+                    // TODO do we have to explicitly wait here if the bus is busy (e.g. another
+                    // master is communicating)?
+
+                    // START and prepare to receive `buffer`
+                    let pec = self.i2c.cr1.read().pecen().bit_is_set();
+                    self.start_chunks(addr, true, buffer.len(), true, pec);
+                    self.read_chunks(buffer, pec)?;
+
+                    // automatic STOP
+
+                    Ok(())
+                }
+
+                fn write_to(&mut self, addr: SlaveAddress, bytes: &[u8]) -> Result<(), Error> {
+                    assert!(!bytes.is_empty());
+
+                    // START and prepare to send `bytes`
+                    let pec = self.i2c.cr1.read().pecen().bit_is_set();
+                    self.start_chunks(addr, false, bytes.len(), true, pec);
+                    self.write_chunks(bytes, pec)?;
 
                     // Wait until the last transmission is finished ???
                     // busy_wait!(self.i2c, busy);
@@ -279,74 +1563,168 @@ macro_rules! hal {
 
                     Ok(())
                 }
-            }
-
-            impl<PINS> WriteRead for I2c<$I2CX, PINS> {
-                type Error = Error;
 
-                fn write_read(
+                fn write_read_with(
                     &mut self,
-                    addr: u8,
+                    addr: SlaveAddress,
                     bytes: &[u8],
                     buffer: &mut [u8],
                 ) -> Result<(), Error> {
-                    // TODO support transfers of more than 255 bytes
-                    assert!(bytes.len() < 256 && bytes.len() > 0);
-                    assert!(buffer.len() < 256 && buffer.len() > 0);
+                    assert!(!bytes.is_empty());
+                    assert!(!buffer.is_empty());
 
                     // TODO do we have to explicitly wait here if the bus is busy (e.g. another
                     // master is communicating)?
 
                     // START and prepare to send `bytes`
+                    self.start_chunks(addr, false, bytes.len(), false, false);
+                    self.write_chunks(bytes, false)?;
+
+                    // Wait until the last byte transmission is finished:
+                    busy_wait!(self.i2c, tc, is_complete);
+
+                    // reSTART and prepare to receive bytes into `buffer`, only
+                    // the header of a 10 bit address is sent again
+                    let addr = match addr {
+                        SlaveAddress::TenBit(addr) => SlaveAddress::TenBitHeader(addr),
+                        addr => addr,
+                    };
+                    let pec = self.i2c.cr1.read().pecen().bit_is_set();
+                    self.start_chunks(addr, true, buffer.len(), true, pec);
+                    self.read_chunks(buffer, pec)?;
+
+                    // automatic STOP
+
+                    Ok(())
+                }
+
+                /// (re)STARTs a transfer of `len` bytes
+                ///
+                /// NBYTES covers at most 255 bytes, longer transfers are continued
+                /// in chunks with RELOAD. `autoend` generates the STOP after the
+                /// last byte, `pec` appends the PEC byte to the transfer.
+                fn start_chunks(
+                    &mut self,
+                    addr: SlaveAddress,
+                    read: bool,
+                    len: usize,
+                    autoend: bool,
+                    pec: bool,
+                ) {
+                    let len = len + usize::from(pec);
+                    let nbytes = cmp::min(len, 255);
+                    let (sadd, add10, head10r) = addr.bits();
                     self.i2c.cr2.modify(|_, w| {
                         w.sadd()
-                            .bits(u16::from(addr << 1))
+                            .bits(sadd)
+                            .add10()
+                            .bit(add10)
+                            .head10r()
+                            .bit(head10r)
                             .rd_wrn()
-                            .write()
+                            .bit(read)
                             .nbytes()
-                            .bits(bytes.len() as u8)
-                            .start()
-                            .start()
+                            .bits(nbytes as u8)
+                            .reload()
+                            .bit(len > 255)
                             .autoend()
-                            .software()
+                            .bit(autoend)
+                            .pecbyte()
+                            .bit(pec)
+                            .start()
+                            .set_bit()
                     });
+                }
 
-                    for byte in bytes {
-                        // Wait until we are allowed to send data
-                        // (START has been ACKed or last byte went through):
-                        busy_wait!(self.i2c, txis, is_empty);
-
-                        // put byte into TXDR
-                        // NOTE(write): writes all non-reserved bits.
-                        self.i2c.txdr.write(|w| w.txdata().bits(*byte));
-                    }
+                /// Continues a transfer, which has reached the end of a chunk
+                /// (TCR), with the next chunk of the `remaining` bytes
+                ///
+                /// `remaining` is reduced by the bytes of the next chunk.
+                fn reload_next_chunk(&self, remaining: &mut usize) {
+                    let nbytes = cmp::min(*remaining, 255);
+                    *remaining -= nbytes;
+                    self.i2c.cr2.modify(|_, w| {
+                        w.nbytes()
+                            .bits(nbytes as u8)
+                            .reload()
+                            .bit(*remaining > 0)
+                    });
+                }
 
-                    // Wait until the last byte transmission is finished:
-                    busy_wait!(self.i2c, tc, is_complete);
+                /// Waits for the end of a chunk and continues with the next one
+                /// of the `remaining` bytes
+                fn reload_chunk(&mut self, remaining: usize) -> Result<(), Error> {
+                    busy_wait!(self.i2c, tcr, bit_is_set);
 
-                    // reSTART and prepare to receive bytes into `buffer`
+                    let nbytes = cmp::min(remaining, 255);
                     self.i2c.cr2.modify(|_, w| {
-                        w.sadd()
-                            .bits(u16::from(addr << 1))
-                            .rd_wrn()
-                            .read()
-                            .nbytes()
-                            .bits(buffer.len() as u8)
-                            .start()
-                            .start()
-                            .autoend()
-                            .automatic()
+                        w.nbytes()
+                            .bits(nbytes as u8)
+                            .reload()
+                            .bit(remaining > 255)
                     });
+                    Ok(())
+                }
+
+                fn read_chunks(&mut self, buffer: &mut [u8], pec: bool) -> Result<(), Error> {
+                    let len = buffer.len() + usize::from(pec);
+                    for (i, chunk) in buffer.chunks_mut(255).enumerate() {
+                        if i > 0 {
+                            self.reload_chunk(len - i * 255)?;
+                        }
 
-                    for byte in buffer {
-                        // Wait until we have received something
+                        for byte in chunk {
+                            // Wait until we have received something
+                            busy_wait!(self.i2c, rxne, is_not_empty);
+
+                            *byte = self.i2c.rxdr.read().rxdata().bits();
+                        }
+                    }
+
+                    if pec {
+                        if len % 255 == 1 {
+                            // The PEC byte starts a chunk of its own
+                            self.reload_chunk(1)?;
+                        }
+
+                        // The PEC byte is compared by hardware, which NACKs it
+                        // and sets PECERR on a mismatch
                         busy_wait!(self.i2c, rxne, is_not_empty);
+                        self.i2c.rxdr.read();
 
-                        *byte = self.i2c.rxdr.read().rxdata().bits();
+                        busy_wait!(self.i2c, stopf, bit_is_set);
+                        self.i2c.icr.write(|w| w.stopcf().set_bit());
+                        if self.i2c.isr.read().pecerr().bit_is_set() {
+                            self.i2c.icr.write(|w| w.peccf().set_bit());
+                            return Err(Error::Pec);
+                        }
                     }
+                    Ok(())
+                }
 
-                    // automatic STOP
+                fn write_chunks(&mut self, bytes: &[u8], pec: bool) -> Result<(), Error> {
+                    let len = bytes.len() + usize::from(pec);
+                    for (i, chunk) in bytes.chunks(255).enumerate() {
+                        if i > 0 {
+                            self.reload_chunk(len - i * 255)?;
+                        }
+
+                        for byte in chunk {
+                            // Wait until we are allowed to send data
+                            // (START has been ACKed or last byte went through)
+                            busy_wait!(self.i2c, txis, is_empty);
 
+                            // put byte into TXDR
+                            // NOTE(write): writes all non-reserved bits.
+                            self.i2c.txdr.write(|w| w.txdata().bits(*byte));
+                        }
+                    }
+
+                    if pec && len % 255 == 1 {
+                        // The PEC byte, which is sent by hardware, starts a chunk
+                        // of its own
+                        self.reload_chunk(1)?;
+                    }
                     Ok(())
                 }
             }
@@ -366,11 +1744,11 @@ macro_rules! hal {
     feature = "stm32f398",
 ))]
 hal! {
-    I2C1: (i2c1, i2c1en, i2c1rst),
-    I2C2: (i2c2, i2c2en, i2c2rst),
+    I2C1: (i2c1, i2c1en, i2c1rst, 20),
+    I2C2: (i2c2, i2c2en, i2c2rst, 21),
 }
 
 #[cfg(feature = "stm32f334")]
 hal! {
-    I2C1: (i2c1, i2c1en, i2c1rst),
+    I2C1: (i2c1, i2c1en, i2c1rst, 20),
 }
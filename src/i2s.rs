@@ -0,0 +1,755 @@
+//! Inter-IC Sound (I2S) interface on SPI2 and SPI3
+//!
+//! The I2S clock is the system clock. Data is exchanged in half-words, so
+//! 24 and 32 bit samples take two transfers, the most significant half-word
+//! first. For full duplex operation, the I2S2ext and I2S3ext blocks add a
+//! second data line in the opposite direction, see `I2s::i2s2ext` and
+//! `I2s::i2s3ext`.
+//!
+//! ```
+//! let mut gpiob = dp.GPIOB.split(&mut rcc.ahb);
+//! let ck = gpiob.pb13.into_af5(&mut gpiob.moder, &mut gpiob.afrh);
+//! let ws = gpiob.pb12.into_af5(&mut gpiob.moder, &mut gpiob.afrh);
+//! let sd = gpiob.pb15.into_af5(&mut gpiob.moder, &mut gpiob.afrh);
+//!
+//! let config = Config::default().sample_rate(48.khz());
+//! let mut i2s = I2s::i2s2(dp.SPI2, (ck, ws, sd), config, clocks, &mut rcc.apb1).unwrap();
+//! i2s.enable();
+//! block!(i2s.send(left)).unwrap();
+//! block!(i2s.send(right)).unwrap();
+//! ```
+
+use core::ptr;
+
+use cortex_m::interrupt;
+
+use crate::dma;
+use crate::gpio::gpioa::PA4;
+use crate::gpio::gpiob::{PB12, PB13, PB14, PB15, PB3, PB5};
+use crate::gpio::gpioc::{PC10, PC11, PC12, PC6, PC7};
+use crate::gpio::{AF5, AF6};
+use crate::pac::{I2S2EXT, I2S3EXT, SPI2, SPI3};
+use crate::rcc::{Clocks, APB1};
+use crate::spi::{Polarity, RxDma, TxDma};
+use crate::time::Hertz;
+
+/// I2S error
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// A received half-word was not read in time
+    Overrun,
+    /// A slave transmitter had no half-word to send
+    Underrun,
+    /// The word select signal of the master changed unexpectedly
+    Frame,
+}
+
+/// Invalid I2S configuration
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ConfigError {
+    /// The sample rate is zero or can not be reached with the I2S clock
+    SampleRate,
+}
+
+/// Role on the bus
+#[derive(Clone, Copy, PartialEq)]
+pub enum Mode {
+    /// Drives the clock and sends data
+    MasterTransmit,
+    /// Drives the clock and receives data
+    MasterReceive,
+    /// Follows an external clock and sends data
+    SlaveTransmit,
+    /// Follows an external clock and receives data
+    SlaveReceive,
+}
+
+impl Mode {
+    fn is_master(self) -> bool {
+        self == Mode::MasterTransmit || self == Mode::MasterReceive
+    }
+
+    fn is_transmit(self) -> bool {
+        self == Mode::MasterTransmit || self == Mode::SlaveTransmit
+    }
+
+    /// Mode of the extension block, which is always a slave and transfers
+    /// data in the opposite direction
+    fn ext(self) -> Self {
+        if self.is_transmit() {
+            Mode::SlaveReceive
+        } else {
+            Mode::SlaveTransmit
+        }
+    }
+}
+
+/// Audio protocol
+#[derive(Clone, Copy, PartialEq)]
+pub enum Standard {
+    /// Philips standard, data is delayed by one clock after WS changes
+    Philips,
+    /// MSB justified, left justified
+    Msb,
+    /// LSB justified, right justified
+    Lsb,
+    /// PCM with a frame synchronization pulse of one clock
+    PcmShort,
+    /// PCM with a frame synchronization pulse of 13 clocks
+    PcmLong,
+}
+
+/// Length of the data and of the channel it is transferred in
+#[derive(Clone, Copy, PartialEq)]
+pub enum Format {
+    /// 16 bit data in a 16 bit channel
+    Data16Channel16,
+    /// 16 bit data in a 32 bit channel
+    Data16Channel32,
+    /// 24 bit data in a 32 bit channel
+    Data24Channel32,
+    /// 32 bit data in a 32 bit channel
+    Data32Channel32,
+}
+
+/// Audio channel of a half-word
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Channel {
+    /// Left channel, WS is low
+    Left,
+    /// Right channel, WS is high
+    Right,
+}
+
+/// I2S settings
+///
+/// The default is a master transmitter using the Philips standard, with
+/// 16 bit data in 16 bit channels, the clock idle low and a sample rate
+/// of 48 kHz.
+#[derive(Clone, Copy)]
+pub struct Config {
+    mode: Mode,
+    standard: Standard,
+    format: Format,
+    polarity: Polarity,
+    sample_rate: Hertz,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            mode: Mode::MasterTransmit,
+            standard: Standard::Philips,
+            format: Format::Data16Channel16,
+            polarity: Polarity::IdleLow,
+            sample_rate: Hertz(48_000),
+        }
+    }
+}
+
+impl Config {
+    /// Sets the role on the bus
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Sets the audio protocol
+    pub fn standard(mut self, standard: Standard) -> Self {
+        self.standard = standard;
+        self
+    }
+
+    /// Sets the data and channel length
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Sets the level of the clock while idle
+    pub fn polarity(mut self, polarity: Polarity) -> Self {
+        self.polarity = polarity;
+        self
+    }
+
+    /// Sets the sample rate of a master
+    ///
+    /// A slave follows the clock of the master, so the rate is ignored.
+    pub fn sample_rate<F>(mut self, sample_rate: F) -> Self
+    where
+        F: Into<Hertz>,
+    {
+        self.sample_rate = sample_rate.into();
+        self
+    }
+
+    /// Conversion to bits for I2SCFGR, I2SE is cleared
+    fn bits(self) -> u32 {
+        // DATLEN and CHLEN
+        let format = match self.format {
+            Format::Data16Channel16 => 0b000,
+            Format::Data16Channel32 => 0b001,
+            Format::Data24Channel32 => 0b011,
+            Format::Data32Channel32 => 0b101,
+        };
+        let ckpol = match self.polarity {
+            Polarity::IdleLow => 0,
+            Polarity::IdleHigh => 1,
+        };
+        let i2sstd = match self.standard {
+            Standard::Philips => 0b00,
+            Standard::Msb => 0b01,
+            Standard::Lsb => 0b10,
+            Standard::PcmShort | Standard::PcmLong => 0b11,
+        };
+        let pcmsync = match self.standard {
+            Standard::PcmLong => 1,
+            _ => 0,
+        };
+        let i2scfg = match self.mode {
+            Mode::SlaveTransmit => 0b00,
+            Mode::SlaveReceive => 0b01,
+            Mode::MasterTransmit => 0b10,
+            Mode::MasterReceive => 0b11,
+        };
+        // I2SMOD selects the I2S mode
+        (0b1 << 11) | (i2scfg << 8) | (pcmsync << 7) | (i2sstd << 4) | (ckpol << 3) | format
+    }
+
+    /// Conversion to bits for I2SPR
+    ///
+    /// The sample rate is `i2sclk / (factor * (2 * I2SDIV + ODD))`, where the
+    /// factor is 256 with the master clock output and the number of bits of
+    /// a frame without it.
+    fn prescaler(self, i2sclk: Hertz, master_clock: bool) -> Result<u32, ConfigError> {
+        let factor = match (master_clock, self.format) {
+            (true, _) => 256,
+            (false, Format::Data16Channel16) => 32,
+            (false, _) => 64,
+        };
+        let rate = match self.sample_rate.0.checked_mul(factor) {
+            Some(0) | None => return Err(ConfigError::SampleRate),
+            Some(rate) => rate,
+        };
+        // Rounded to the nearest divider
+        let div = (i2sclk.0 + rate / 2) / rate;
+        if !(4..=511).contains(&div) {
+            return Err(ConfigError::SampleRate);
+        }
+
+        // MCKOE, ODD and I2SDIV
+        Ok((u32::from(master_clock) << 9) | ((div & 0b1) << 8) | (div >> 1))
+    }
+}
+
+/// Clock pin
+pub unsafe trait CkPin<SPI> {}
+
+/// Word select pin
+pub unsafe trait WsPin<SPI> {}
+
+/// Serial data pin
+pub unsafe trait SdPin<SPI> {}
+
+/// Master clock output pin
+pub unsafe trait MckPin<SPI> {}
+
+/// Serial data pin of an extension block
+pub unsafe trait ExtSdPin<EXT> {}
+
+unsafe impl CkPin<SPI2> for PB13<AF5> {}
+unsafe impl CkPin<SPI3> for PB3<AF6> {}
+unsafe impl CkPin<SPI3> for PC10<AF6> {}
+
+unsafe impl WsPin<SPI2> for PB12<AF5> {}
+unsafe impl WsPin<SPI3> for PA4<AF6> {}
+// unsafe impl WsPin<SPI3> for PA15<AF6> {}
+
+unsafe impl SdPin<SPI2> for PB15<AF5> {}
+unsafe impl SdPin<SPI3> for PB5<AF6> {}
+unsafe impl SdPin<SPI3> for PC12<AF6> {}
+
+unsafe impl ExtSdPin<I2S2EXT> for PB14<AF5> {}
+// unsafe impl ExtSdPin<I2S3EXT> for PB4<AF6> {}
+unsafe impl ExtSdPin<I2S3EXT> for PC11<AF6> {}
+
+// unsafe impl MckPin<SPI2> for PA8<AF5> {}
+unsafe impl MckPin<SPI2> for PC6<AF6> {}
+unsafe impl MckPin<SPI3> for PC7<AF6> {}
+
+/// Pins of an I2S, with or without the master clock output
+///
+/// `(CK, WS, SD)` or `(CK, WS, SD, MCK)`
+pub unsafe trait Pins<SPI> {
+    #[doc(hidden)]
+    const MCK: bool;
+}
+
+unsafe impl<SPI, CK, WS, SD> Pins<SPI> for (CK, WS, SD)
+where
+    CK: CkPin<SPI>,
+    WS: WsPin<SPI>,
+    SD: SdPin<SPI>,
+{
+    const MCK: bool = false;
+}
+
+unsafe impl<SPI, CK, WS, SD, MCK> Pins<SPI> for (CK, WS, SD, MCK)
+where
+    CK: CkPin<SPI>,
+    WS: WsPin<SPI>,
+    SD: SdPin<SPI>,
+    MCK: MckPin<SPI>,
+{
+    const MCK: bool = true;
+}
+
+/// I2S peripheral
+pub struct I2s<SPI, PINS> {
+    spi: SPI,
+    pins: PINS,
+    config: Config,
+}
+
+/// I2S extension block for full duplex operation
+///
+/// It shares the clock and word select pins with its `I2s` and runs as a
+/// slave in the opposite direction. Returned by `I2s::i2s2ext` and
+/// `I2s::i2s3ext`.
+pub struct I2sExt<EXT, SD> {
+    spi: EXT,
+    sd: SD,
+    config: Config,
+}
+
+/// A DMA transfer from or to the I2S
+///
+/// Returned by `write_dma` and `read_dma` of `I2s` and `I2sExt`.
+pub struct DmaTransfer<I2S, B, C, T>
+where
+    C: dma::Channel,
+    T: dma::Target,
+{
+    i2s: I2S,
+    transfer: dma::Transfer<B, C, T>,
+}
+
+impl<I2S, B, C, T> DmaTransfer<I2S, B, C, T>
+where
+    C: dma::Channel,
+    T: dma::Target,
+{
+    /// Is this transfer complete?
+    pub fn is_complete(&self) -> bool {
+        self.transfer.is_complete()
+    }
+
+    /// Stop this transfer and return ownership over its parts
+    ///
+    /// The I2S stays enabled.
+    pub fn stop(self) -> (B, C, I2S) {
+        let (buffer, channel, _) = self.transfer.stop();
+        (buffer, channel, self.i2s)
+    }
+
+    /// Block until this transfer is done and return ownership over its parts
+    pub fn wait(self) -> (B, C, I2S) {
+        let (buffer, channel, _) = self.transfer.wait();
+        (buffer, channel, self.i2s)
+    }
+}
+
+/// A circular DMA reception or transmission with a double buffer
+///
+/// Returned by `read_dma_circular` and `write_dma_circular` of `I2s` and
+/// `I2sExt`.
+pub struct DmaStream<I2S, B, C, T>
+where
+    B: 'static,
+    C: dma::Channel,
+    T: dma::Target,
+{
+    i2s: I2S,
+    transfer: dma::CircTransfer<B, C, T>,
+}
+
+impl<I2S, SPI, B, C> DmaStream<I2S, B, C, RxDma<SPI>>
+where
+    C: dma::Channel,
+    RxDma<SPI>: dma::Target,
+{
+    /// Call `f` with the next half of the buffer, which has been filled
+    ///
+    /// See [`CircTransfer::peek`](../dma/struct.CircTransfer.html#method.peek).
+    pub fn peek<R, F>(&mut self, f: F) -> nb::Result<R, dma::Error>
+    where
+        F: FnOnce(&B, dma::Half) -> R,
+    {
+        self.transfer.peek(f)
+    }
+}
+
+impl<I2S, SPI, B, C> DmaStream<I2S, B, C, TxDma<SPI>>
+where
+    C: dma::Channel,
+    TxDma<SPI>: dma::Target,
+{
+    /// Call `f` to refill the next half of the buffer, which has been sent
+    ///
+    /// See [`CircTransfer::fill`](../dma/struct.CircTransfer.html#method.fill).
+    pub fn fill<R, F>(&mut self, f: F) -> nb::Result<R, dma::Error>
+    where
+        F: FnOnce(&mut B, dma::Half) -> R,
+    {
+        self.transfer.fill(f)
+    }
+}
+
+impl<I2S, B, C, T> DmaStream<I2S, B, C, T>
+where
+    C: dma::Channel,
+    T: dma::Target,
+{
+    /// Stop the stream and return ownership over its parts
+    ///
+    /// The I2S stays enabled.
+    pub fn stop(self) -> (&'static mut [B; 2], C, I2S) {
+        let (buffer, channel, _) = self.transfer.stop();
+        (buffer, channel, self.i2s)
+    }
+}
+
+/// Methods shared by `I2s` and `I2sExt`, which use the registers of `$SPIX`
+macro_rules! data {
+    ($SPIX:ident) => {
+        /// Enables the I2S
+        ///
+        /// A master starts to drive the clock.
+        pub fn enable(&mut self) {
+            // NOTE(unsafe) I2SE is set, the configuration is kept
+            self.spi
+                .i2scfgr
+                .modify(|r, w| unsafe { w.bits(r.bits() | (0b1 << 10)) });
+        }
+
+        /// Disables the I2S
+        ///
+        /// A transmitter waits until the last half-word is sent.
+        pub fn disable(&mut self) {
+            if self.config.mode.is_transmit() {
+                while self.spi.sr.read().txe().is_not_empty() {}
+                while self.spi.sr.read().bsy().is_busy() {}
+            }
+            // NOTE(unsafe) I2SE is cleared, the configuration is kept
+            self.spi
+                .i2scfgr
+                .modify(|r, w| unsafe { w.bits(r.bits() & !(0b1 << 10)) });
+        }
+
+        /// Writes the next half-word to send
+        pub fn send(&mut self, data: u16) -> nb::Result<(), Error> {
+            // Reading SR clears UDR and FRE
+            let sr = self.spi.sr.read();
+
+            Err(if sr.udr().bit_is_set() {
+                nb::Error::Other(Error::Underrun)
+            } else if sr.fre().bit_is_set() {
+                nb::Error::Other(Error::Frame)
+            } else if sr.txe().is_empty() {
+                // NOTE(unsafe) write to register owned by this struct
+                unsafe { ptr::write_volatile(&self.spi.dr as *const _ as *mut u16, data) };
+                return Ok(());
+            } else {
+                nb::Error::WouldBlock
+            })
+        }
+
+        /// Reads the next received half-word
+        pub fn read(&mut self) -> nb::Result<u16, Error> {
+            let sr = self.spi.sr.read();
+
+            Err(if sr.ovr().is_overrun() {
+                // Reading DR and then SR clears OVR
+                // NOTE(unsafe) read from register owned by this struct
+                unsafe { ptr::read_volatile(&self.spi.dr as *const _ as *const u16) };
+                self.spi.sr.read();
+                nb::Error::Other(Error::Overrun)
+            } else if sr.fre().bit_is_set() {
+                nb::Error::Other(Error::Frame)
+            } else if sr.rxne().is_not_empty() {
+                // NOTE(unsafe) read from register owned by this struct
+                let data = unsafe { ptr::read_volatile(&self.spi.dr as *const _ as *const u16) };
+                return Ok(data);
+            } else {
+                nb::Error::WouldBlock
+            })
+        }
+
+        /// Returns the channel of the half-word, which is sent next or
+        /// has been received last
+        ///
+        /// The channel is not defined in the PCM standards.
+        pub fn channel(&self) -> Channel {
+            if self.spi.sr.read().chside().bit_is_set() {
+                Channel::Right
+            } else {
+                Channel::Left
+            }
+        }
+
+        /// Sends all half-words of the buffer using DMA
+        ///
+        /// # Panics
+        ///
+        /// Panics if the I2S is configured as a receiver.
+        pub fn write_dma<B, C>(
+            self,
+            buffer: B,
+            mut channel: C,
+        ) -> DmaTransfer<Self, B, C, TxDma<$SPIX>>
+        where
+            B: dma::ReadBuffer<Word = u16> + 'static,
+            C: dma::Channel,
+            TxDma<$SPIX>: dma::OnChannel<C>,
+        {
+            assert!(
+                self.config.mode.is_transmit(),
+                "I2S is configured as a receiver"
+            );
+
+            let pa = &self.spi.dr as *const _ as u32;
+            channel.set_peripheral_address(pa, dma::Increment::Disable);
+
+            let transfer = dma::Transfer::start_read(buffer, channel, TxDma::new());
+
+            DmaTransfer {
+                i2s: self,
+                transfer,
+            }
+        }
+
+        /// Receives half-words into the buffer using DMA, until it is full
+        ///
+        /// # Panics
+        ///
+        /// Panics if the I2S is configured as a transmitter.
+        pub fn read_dma<B, C>(
+            self,
+            buffer: B,
+            mut channel: C,
+        ) -> DmaTransfer<Self, B, C, RxDma<$SPIX>>
+        where
+            B: dma::WriteBuffer<Word = u16> + 'static,
+            C: dma::Channel,
+            RxDma<$SPIX>: dma::OnChannel<C>,
+        {
+            assert!(
+                !self.config.mode.is_transmit(),
+                "I2S is configured as a transmitter"
+            );
+
+            let pa = &self.spi.dr as *const _ as u32;
+            channel.set_peripheral_address(pa, dma::Increment::Disable);
+
+            let transfer = dma::Transfer::start_write(buffer, channel, RxDma::new());
+
+            DmaTransfer {
+                i2s: self,
+                transfer,
+            }
+        }
+
+        /// Receives half-words continuously into both halves of the
+        /// buffer using DMA
+        ///
+        /// # Panics
+        ///
+        /// Panics if the I2S is configured as a transmitter.
+        pub fn read_dma_circular<B, C>(
+            self,
+            buffer: &'static mut [B; 2],
+            mut channel: C,
+        ) -> DmaStream<Self, B, C, RxDma<$SPIX>>
+        where
+            B: dma::WriteTarget<Word = u16>,
+            C: dma::Channel,
+            RxDma<$SPIX>: dma::OnChannel<C>,
+        {
+            assert!(
+                !self.config.mode.is_transmit(),
+                "I2S is configured as a transmitter"
+            );
+
+            let pa = &self.spi.dr as *const _ as u32;
+            channel.set_peripheral_address(pa, dma::Increment::Disable);
+
+            let transfer = dma::CircTransfer::start(buffer, channel, RxDma::new());
+
+            DmaStream {
+                i2s: self,
+                transfer,
+            }
+        }
+
+        /// Sends half-words continuously from both halves of the
+        /// buffer using DMA
+        ///
+        /// Both halves should be filled before, the sent halves are
+        /// refilled with [`DmaStream::fill`](struct.DmaStream.html#method.fill).
+        ///
+        /// # Panics
+        ///
+        /// Panics if the I2S is configured as a receiver.
+        pub fn write_dma_circular<B, C>(
+            self,
+            buffer: &'static mut [B; 2],
+            mut channel: C,
+        ) -> DmaStream<Self, B, C, TxDma<$SPIX>>
+        where
+            B: dma::WriteTarget<Word = u16>,
+            C: dma::Channel,
+            TxDma<$SPIX>: dma::OnChannel<C>,
+        {
+            assert!(
+                self.config.mode.is_transmit(),
+                "I2S is configured as a receiver"
+            );
+
+            let pa = &self.spi.dr as *const _ as u32;
+            channel.set_peripheral_address(pa, dma::Increment::Disable);
+
+            let transfer = dma::CircTransfer::start_read(buffer, channel, TxDma::new());
+
+            DmaStream {
+                i2s: self,
+                transfer,
+            }
+        }
+    };
+}
+
+macro_rules! hal {
+    ($($SPIX:ident: ($i2sX:ident, $spiXen:ident, $spiXrst:ident, $EXT:ident, $i2sXext:ident),)+) => {
+        $(
+            impl<PINS> I2s<$SPIX, PINS> {
+                /// Configures the SPI peripheral to operate in I2S mode
+                ///
+                /// The I2S is left disabled, see [`enable`](#method.enable).
+                /// The master clock output is enabled for a master, if the
+                /// pins include the MCK pin.
+                ///
+                /// A sample rate, which a master can not reach with the system
+                /// clock, is rejected before the SPI peripheral is enabled.
+                pub fn $i2sX(
+                    spi: $SPIX,
+                    pins: PINS,
+                    config: Config,
+                    clocks: Clocks,
+                    apb1: &mut APB1,
+                ) -> Result<Self, ConfigError>
+                where
+                    PINS: Pins<$SPIX>,
+                {
+                    let i2spr = if config.mode.is_master() {
+                        config.prescaler(clocks.sysclk(), PINS::MCK)?
+                    } else {
+                        // Reset value, the prescaler is not used by a slave
+                        0b10
+                    };
+
+                    // The extension block is reset along with the SPI
+                    apb1.enr().modify(|_, w| w.$spiXen().enabled());
+                    apb1.rstr().modify(|_, w| w.$spiXrst().reset());
+                    apb1.rstr().modify(|_, w| w.$spiXrst().clear_bit());
+
+                    // NOTE(unsafe) I2SPR and I2SCFGR are written while I2SE is
+                    // cleared, all bits are valid
+                    spi.i2spr.write(|w| unsafe { w.bits(i2spr) });
+                    spi.i2scfgr.write(|w| unsafe { w.bits(config.bits()) });
+
+                    Ok(I2s { spi, pins, config })
+                }
+
+                /// Configures the extension block for full duplex operation
+                ///
+                /// The extension block transfers data on its own SD pin in the
+                /// opposite direction of this I2S, as a slave of its clock and
+                /// word select signals. Enable it before this I2S, so it does
+                /// not miss the first frame.
+                pub fn $i2sXext<SD>(&mut self, ext: $EXT, sd: SD) -> I2sExt<$EXT, SD>
+                where
+                    SD: ExtSdPin<$EXT>,
+                {
+                    let config = self.config.mode(self.config.mode.ext());
+
+                    // NOTE(unsafe) I2SCFGR is written while I2SE is cleared,
+                    // all bits are valid
+                    ext.i2scfgr.write(|w| unsafe { w.bits(config.bits()) });
+
+                    I2sExt { spi: ext, sd, config }
+                }
+
+                data!($SPIX);
+
+                /// Disables the I2S and releases the SPI peripheral and the pins
+                pub fn free(mut self) -> ($SPIX, PINS) {
+                    self.disable();
+                    (self.spi, self.pins)
+                }
+            }
+
+            impl<SD> I2sExt<$EXT, SD> {
+                data!($EXT);
+
+                /// Disables the extension block and releases it and its SD pin
+                pub fn free(mut self) -> ($EXT, SD) {
+                    self.disable();
+                    (self.spi, self.sd)
+                }
+            }
+
+            impl dma::Target for RxDma<$EXT> {
+                fn enable_dma(&mut self) {
+                    // NOTE(unsafe) critical section prevents races
+                    interrupt::free(|_| unsafe {
+                        let cr2 = &(*$EXT::ptr()).cr2;
+                        cr2.modify(|_, w| w.rxdmaen().set_bit());
+                    });
+                }
+
+                fn disable_dma(&mut self) {
+                    // NOTE(unsafe) critical section prevents races
+                    interrupt::free(|_| unsafe {
+                        let cr2 = &(*$EXT::ptr()).cr2;
+                        cr2.modify(|_, w| w.rxdmaen().clear_bit());
+                    });
+                }
+            }
+
+            impl dma::Target for TxDma<$EXT> {
+                fn enable_dma(&mut self) {
+                    // NOTE(unsafe) critical section prevents races
+                    interrupt::free(|_| unsafe {
+                        let cr2 = &(*$EXT::ptr()).cr2;
+                        cr2.modify(|_, w| w.txdmaen().set_bit());
+                    });
+                }
+
+                fn disable_dma(&mut self) {
+                    // NOTE(unsafe) critical section prevents races
+                    interrupt::free(|_| unsafe {
+                        let cr2 = &(*$EXT::ptr()).cr2;
+                        cr2.modify(|_, w| w.txdmaen().clear_bit());
+                    });
+                }
+            }
+        )+
+    }
+}
+
+hal! {
+    SPI2: (i2s2, spi2en, spi2rst, I2S2EXT, i2s2ext),
+    SPI3: (i2s3, spi3en, spi3rst, I2S3EXT, i2s3ext),
+}
@@ -108,11 +108,24 @@ pub use crate::pac as stm32;
 #[cfg(feature = "rt")]
 pub use crate::pac::interrupt;
 
-#[cfg(feature = "stm32f303")]
+#[cfg(all(
+    feature = "device-selected",
+    not(any(feature = "stm32f373", feature = "stm32f378"))
+))]
 pub mod adc;
+#[cfg(any(feature = "stm32f373", feature = "stm32f378"))]
+#[path = "adc_legacy.rs"]
+pub mod adc;
+#[cfg(any(
+    feature = "stm32f303",
+    feature = "stm32f334",
+    feature = "stm32f373",
+    feature = "stm32f378"
+))]
+pub mod dac;
 #[cfg(feature = "device-selected")]
 pub mod delay;
-#[cfg(feature = "stm32f303")]
+#[cfg(feature = "device-selected")]
 pub mod dma;
 #[cfg(feature = "device-selected")]
 pub mod flash;
@@ -120,6 +133,16 @@ pub mod flash;
 pub mod gpio;
 #[cfg(feature = "device-selected")]
 pub mod i2c;
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398"
+))]
+pub mod i2s;
 #[cfg(feature = "device-selected")]
 pub mod prelude;
 #[cfg(feature = "device-selected")]
@@ -127,10 +150,16 @@ pub mod pwm;
 #[cfg(feature = "device-selected")]
 pub mod rcc;
 #[cfg(feature = "device-selected")]
+pub mod rtc;
+#[cfg(any(feature = "stm32f373", feature = "stm32f378"))]
+pub mod sdadc;
+#[cfg(feature = "device-selected")]
 pub mod serial;
 #[cfg(feature = "device-selected")]
 pub mod spi;
 #[cfg(feature = "device-selected")]
+pub mod syscfg;
+#[cfg(feature = "device-selected")]
 pub mod time;
 #[cfg(feature = "device-selected")]
 pub mod timer;
@@ -20,6 +20,7 @@ impl RccExt for RCC {
             ahb: AHB { _0: () },
             apb1: APB1 { _0: () },
             apb2: APB2 { _0: () },
+            bdcr: BDCR { _0: () },
             cfgr: CFGR {
                 hse: None,
                 hclk: None,
@@ -48,6 +49,8 @@ pub struct Rcc {
     pub apb1: APB1,
     /// Advanced Peripheral Bus 2 (APB2) registers
     pub apb2: APB2,
+    /// RCC Backup Domain
+    pub bdcr: BDCR,
     /// Clock configuration
     pub cfgr: CFGR,
 }
@@ -127,6 +130,26 @@ impl APB2 {
     }
 }
 
+/// Backup Domain Control register (RCC_BDCR)
+///
+/// An instance of this struct is aquired from the [Rcc](../struct.Rcc.html) struct.
+///
+/// ```
+/// let dp = pac::Peripherals::take().unwrap();
+/// let rcc = dp.RCC.constrain();
+/// use_bdcr(&mut rcc.bdcr)
+/// ```
+pub struct BDCR {
+    _0: (),
+}
+
+impl BDCR {
+    pub(crate) fn bdcr(&mut self) -> &rcc::BDCR {
+        // NOTE(unsafe) this proxy grants exclusive access to this register
+        unsafe { &(*RCC::ptr()).bdcr }
+    }
+}
+
 const HSI: u32 = 8_000_000; // Hz
 
 // some microcontrollers do not have USB
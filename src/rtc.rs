@@ -0,0 +1,544 @@
+//! Real Time Clock
+//!
+//! The RTC lives in the backup domain and keeps its calendar running through
+//! resets and, if a battery is connected to VBAT, through power loss.
+//!
+//! ```
+//! let dp = pac::Peripherals::take().unwrap();
+//! let mut rcc = dp.RCC.constrain();
+//! let mut pwr = dp.PWR;
+//!
+//! let mut rtc = Rtc::new(
+//!     dp.RTC,
+//!     ClockSource::Lse { bypass: false },
+//!     &mut rcc.apb1,
+//!     &mut rcc.bdcr,
+//!     &mut pwr,
+//! );
+//!
+//! rtc.set_date(&Date { year: 2020, month: 8, day: 8, weekday: 6 }).unwrap();
+//! rtc.set_time(&Time { hours: 12, minutes: 0, seconds: 0 }).unwrap();
+//! ```
+
+use core::cell::UnsafeCell;
+use core::ptr;
+
+use crate::pac::{EXTI, PWR, RTC};
+use crate::rcc::{APB1, BDCR};
+
+/// EXTI line connected to the RTC alarms
+const EXTI_LINE_ALARM: u32 = 17;
+/// EXTI line connected to the RTC wakeup timer
+const EXTI_LINE_WAKEUP: u32 = 20;
+
+/// Offset of the first backup register in the RTC register block
+const BKP_OFFSET: usize = 0x50;
+/// Number of backup registers
+#[cfg(not(any(feature = "stm32f373", feature = "stm32f378")))]
+const BKP_COUNT: usize = 16;
+#[cfg(any(feature = "stm32f373", feature = "stm32f378"))]
+const BKP_COUNT: usize = 32;
+
+/// Flags of ISR, which are cleared by writing 0: RSF, ALRAF, ALRBF, WUTF,
+/// TSF, TSOVF and TAMPxF
+const ISR_RC_W0: u32 = 0xFF20;
+
+/// RTC error
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// A date, time or interval value is out of range
+    InvalidInputData,
+}
+
+/// Clock source of the RTC
+pub enum ClockSource {
+    /// Low speed external oscillator (32.768 kHz)
+    ///
+    /// If `bypass` is set, an external clock signal is expected on OSC32_IN
+    /// instead of a crystal.
+    Lse { bypass: bool },
+    /// Low speed internal RC oscillator (40 kHz)
+    ///
+    /// The LSI is not very accurate, so expect the calendar to drift.
+    Lsi,
+}
+
+/// Calendar date
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Date {
+    /// Year in the range 2000..=2099
+    pub year: u16,
+    /// Month in the range 1..=12
+    pub month: u8,
+    /// Day of the month in the range 1..=31
+    pub day: u8,
+    /// Day of the week in the range 1..=7, with Monday being 1
+    pub weekday: u8,
+}
+
+/// Time of the day (24 hour format)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Time {
+    /// Hours in the range 0..=23
+    pub hours: u8,
+    /// Minutes in the range 0..=59
+    pub minutes: u8,
+    /// Seconds in the range 0..=59
+    pub seconds: u8,
+}
+
+/// RTC alarms
+#[derive(Clone, Copy, PartialEq)]
+pub enum Alarm {
+    /// Alarm A
+    A,
+    /// Alarm B
+    B,
+}
+
+/// Condition which fires an alarm
+///
+/// Every field, which is `None`, is ignored when comparing the alarm
+/// against the calendar. E.g. an alarm with only `seconds` set fires every
+/// minute.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AlarmMatch {
+    /// Day of the month in the range 1..=31
+    pub day: Option<u8>,
+    /// Hours in the range 0..=23
+    pub hours: Option<u8>,
+    /// Minutes in the range 0..=59
+    pub minutes: Option<u8>,
+    /// Seconds in the range 0..=59
+    pub seconds: Option<u8>,
+}
+
+/// Interrupt events
+#[derive(Clone, Copy, PartialEq)]
+pub enum Event {
+    /// Alarm A fired
+    AlarmA,
+    /// Alarm B fired
+    AlarmB,
+    /// The wakeup timer elapsed
+    Wakeup,
+}
+
+/// A single 32 bit backup register
+///
+/// The backup registers keep their content as long as VBAT is powered and
+/// are reset together with the backup domain.
+#[repr(transparent)]
+pub struct BackupRegister(UnsafeCell<u32>);
+
+impl BackupRegister {
+    /// Reads the value of this register
+    pub fn read(&self) -> u32 {
+        // NOTE(unsafe) volatile read of a memory mapped register
+        unsafe { ptr::read_volatile(self.0.get()) }
+    }
+
+    /// Writes a value to this register
+    pub fn write(&mut self, value: u32) {
+        // NOTE(unsafe) volatile write to a memory mapped register
+        unsafe { ptr::write_volatile(self.0.get(), value) }
+    }
+}
+
+/// Real Time Clock peripheral
+pub struct Rtc {
+    rtc: RTC,
+}
+
+impl Rtc {
+    /// Configures the RTC with the given clock source and enables it
+    ///
+    /// If the RTC is already running from the same clock source, e.g. after a
+    /// reset of the core, the calendar is left untouched. Otherwise the
+    /// backup domain is reset, which also clears the backup registers.
+    pub fn new(
+        rtc: RTC,
+        source: ClockSource,
+        apb1: &mut APB1,
+        bdcr: &mut BDCR,
+        pwr: &mut PWR,
+    ) -> Self {
+        // Enable write access to the backup domain
+        apb1.enr().modify(|_, w| w.pwren().set_bit());
+        pwr.cr.modify(|_, w| w.dbp().set_bit());
+        while pwr.cr.read().dbp().bit_is_clear() {}
+
+        let (rtcsel, prediv_a, prediv_s) = match source {
+            // 32_768 Hz / (127 + 1) / (255 + 1) = 1 Hz
+            ClockSource::Lse { .. } => (0b01, 127, 255),
+            // 40_000 Hz / (127 + 1) / (311 + 1) ~= 1 Hz
+            ClockSource::Lsi => (0b10, 127, 311),
+        };
+
+        let bdcr = bdcr.bdcr();
+        let running = bdcr.read().rtcen().bit_is_set();
+        let current_rtcsel = (bdcr.read().bits() >> 8) & 0b11;
+
+        if !running || current_rtcsel != rtcsel {
+            // The clock source can only be changed after a backup domain reset
+            bdcr.modify(|_, w| w.bdrst().set_bit());
+            bdcr.modify(|_, w| w.bdrst().clear_bit());
+
+            match source {
+                ClockSource::Lse { bypass } => {
+                    bdcr.modify(|_, w| w.lsebyp().bit(bypass).lseon().set_bit());
+                    while bdcr.read().lserdy().bit_is_clear() {}
+                }
+                ClockSource::Lsi => {
+                    // NOTE(unsafe) the LSI bits of CSR are not used by any other abstraction
+                    let csr = unsafe { &(*crate::pac::RCC::ptr()).csr };
+                    csr.modify(|_, w| w.lsion().set_bit());
+                    while csr.read().lsirdy().bit_is_clear() {}
+                }
+            }
+
+            // NOTE(unsafe) only the RTCSEL bits are modified
+            bdcr.modify(|r, w| unsafe { w.bits((r.bits() & !(0b11 << 8)) | (rtcsel << 8)) });
+            bdcr.modify(|_, w| w.rtcen().set_bit());
+
+            let mut rtc = Rtc { rtc };
+            rtc.modify(|rtc| {
+                // Use the 24 hour format
+                rtc.cr.modify(|_, w| w.fmt().clear_bit());
+                // The asynchronous prescaler has to be written in a separate access
+                // NOTE(unsafe) writes all non-reserved bits
+                rtc.prer.write(|w| unsafe { w.bits(prediv_s) });
+                rtc.prer
+                    .write(|w| unsafe { w.bits((prediv_a << 16) | prediv_s) });
+            });
+            rtc
+        } else {
+            Rtc { rtc }
+        }
+    }
+
+    /// Sets the date
+    pub fn set_date(&mut self, date: &Date) -> Result<(), Error> {
+        if !(2000..=2099).contains(&date.year)
+            || !(1..=12).contains(&date.month)
+            || !(1..=31).contains(&date.day)
+            || !(1..=7).contains(&date.weekday)
+        {
+            return Err(Error::InvalidInputData);
+        }
+
+        let (yt, yu) = bcd2_encode(u32::from(date.year - 2000));
+        let (mt, mu) = bcd2_encode(u32::from(date.month));
+        let (dt, du) = bcd2_encode(u32::from(date.day));
+        let wdu = u32::from(date.weekday);
+
+        let bits = (yt << 20) | (yu << 16) | (wdu << 13) | (mt << 12) | (mu << 8) | (dt << 4) | du;
+        self.modify(|rtc| {
+            // NOTE(unsafe) writes all non-reserved bits
+            rtc.dr.write(|w| unsafe { w.bits(bits) });
+        });
+
+        Ok(())
+    }
+
+    /// Sets the time of the day
+    pub fn set_time(&mut self, time: &Time) -> Result<(), Error> {
+        if time.hours > 23 || time.minutes > 59 || time.seconds > 59 {
+            return Err(Error::InvalidInputData);
+        }
+
+        let bits = time_bits(time.hours, time.minutes, time.seconds);
+        self.modify(|rtc| {
+            // NOTE(unsafe) writes all non-reserved bits, PM is cleared (24 hour format)
+            rtc.tr.write(|w| unsafe { w.bits(bits) });
+        });
+
+        Ok(())
+    }
+
+    /// Returns the current date
+    pub fn date(&mut self) -> Date {
+        self.date_time().0
+    }
+
+    /// Returns the current time of the day
+    pub fn time(&mut self) -> Time {
+        self.date_time().1
+    }
+
+    /// Returns the current date and time of the day
+    ///
+    /// Both are read from the same snapshot of the calendar, so they are
+    /// consistent with each other.
+    pub fn date_time(&mut self) -> (Date, Time) {
+        // Reading TR locks the shadow register of DR until it is read.
+        let tr = self.rtc.tr.read().bits();
+        let dr = self.rtc.dr.read().bits();
+
+        let date = Date {
+            year: 2000 + bcd2_decode((dr >> 20) & 0xF, (dr >> 16) & 0xF) as u16,
+            month: bcd2_decode((dr >> 12) & 0x1, (dr >> 8) & 0xF) as u8,
+            day: bcd2_decode((dr >> 4) & 0x3, dr & 0xF) as u8,
+            weekday: ((dr >> 13) & 0x7) as u8,
+        };
+        let time = Time {
+            hours: bcd2_decode((tr >> 20) & 0x3, (tr >> 16) & 0xF) as u8,
+            minutes: bcd2_decode((tr >> 12) & 0x7, (tr >> 8) & 0xF) as u8,
+            seconds: bcd2_decode((tr >> 4) & 0x7, tr & 0xF) as u8,
+        };
+
+        (date, time)
+    }
+
+    /// Configures an alarm
+    ///
+    /// The alarm is enabled afterwards. Use [`listen`](#method.listen) to get
+    /// an interrupt, when it fires.
+    pub fn set_alarm(&mut self, alarm: Alarm, when: &AlarmMatch) -> Result<(), Error> {
+        let in_range = |value: Option<u8>, range: core::ops::RangeInclusive<u8>| {
+            value.map_or(true, |v| range.contains(&v))
+        };
+        if !in_range(when.day, 1..=31)
+            || !in_range(when.hours, 0..=23)
+            || !in_range(when.minutes, 0..=59)
+            || !in_range(when.seconds, 0..=59)
+        {
+            return Err(Error::InvalidInputData);
+        }
+
+        // Every field is either compared (MSKx = 0) or masked (MSKx = 1).
+        let field = |value: Option<u8>, msk_bit: u32, shift: u32| match value {
+            Some(v) => {
+                let (tens, units) = bcd2_encode(u32::from(v));
+                ((tens << 4) | units) << shift
+            }
+            None => 1 << msk_bit,
+        };
+        let bits = field(when.day, 31, 24)
+            | field(when.hours, 23, 16)
+            | field(when.minutes, 15, 8)
+            | field(when.seconds, 7, 0);
+
+        self.unprotect(|rtc| match alarm {
+            Alarm::A => {
+                rtc.cr.modify(|_, w| w.alrae().clear_bit());
+                while rtc.isr.read().alrawf().bit_is_clear() {}
+                // NOTE(unsafe) writes all bits of this register, WDSEL is cleared (date match)
+                rtc.alrmar.write(|w| unsafe { w.bits(bits) });
+                rtc.cr.modify(|_, w| w.alrae().set_bit());
+            }
+            Alarm::B => {
+                rtc.cr.modify(|_, w| w.alrbe().clear_bit());
+                while rtc.isr.read().alrbwf().bit_is_clear() {}
+                // NOTE(unsafe) writes all bits of this register, WDSEL is cleared (date match)
+                rtc.alrmbr.write(|w| unsafe { w.bits(bits) });
+                rtc.cr.modify(|_, w| w.alrbe().set_bit());
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Disables an alarm
+    pub fn disable_alarm(&mut self, alarm: Alarm) {
+        self.unprotect(|rtc| match alarm {
+            Alarm::A => rtc.cr.modify(|_, w| w.alrae().clear_bit()),
+            Alarm::B => rtc.cr.modify(|_, w| w.alrbe().clear_bit()),
+        });
+    }
+
+    /// Enables the periodic wakeup timer with an interval of `seconds`
+    ///
+    /// The interval has to be in the range 1..=65536 seconds.
+    pub fn enable_wakeup(&mut self, seconds: u32) -> Result<(), Error> {
+        if !(1..=0x1_0000).contains(&seconds) {
+            return Err(Error::InvalidInputData);
+        }
+
+        self.unprotect(|rtc| {
+            rtc.cr.modify(|_, w| w.wute().clear_bit());
+            while rtc.isr.read().wutwf().bit_is_clear() {}
+            // NOTE(unsafe) the range of seconds has been checked above
+            rtc.wutr.write(|w| unsafe { w.bits(seconds - 1) });
+            // WUCKSEL = 0b100: ck_spre (usually 1 Hz)
+            // NOTE(unsafe) only the WUCKSEL bits are modified
+            rtc.cr
+                .modify(|r, w| unsafe { w.bits((r.bits() & !0b111) | 0b100) });
+            rtc.cr.modify(|_, w| w.wute().set_bit());
+        });
+
+        Ok(())
+    }
+
+    /// Disables the periodic wakeup timer
+    pub fn disable_wakeup(&mut self) {
+        self.unprotect(|rtc| rtc.cr.modify(|_, w| w.wute().clear_bit()));
+    }
+
+    /// Starts listening for an interrupt event
+    ///
+    /// The RTC events are routed through the EXTI, so its line is configured
+    /// as well.
+    pub fn listen(&mut self, exti: &mut EXTI, event: Event) {
+        let line = 1 << Self::exti_line(event);
+        // NOTE(unsafe) only the bit of the EXTI line of this event is modified
+        unsafe {
+            exti.imr1.modify(|r, w| w.bits(r.bits() | line));
+            exti.rtsr1.modify(|r, w| w.bits(r.bits() | line));
+            exti.ftsr1.modify(|r, w| w.bits(r.bits() & !line));
+        }
+
+        self.unprotect(|rtc| match event {
+            Event::AlarmA => rtc.cr.modify(|_, w| w.alraie().set_bit()),
+            Event::AlarmB => rtc.cr.modify(|_, w| w.alrbie().set_bit()),
+            Event::Wakeup => rtc.cr.modify(|_, w| w.wutie().set_bit()),
+        });
+    }
+
+    /// Stops listening for an interrupt event
+    pub fn unlisten(&mut self, exti: &mut EXTI, event: Event) {
+        self.unprotect(|rtc| match event {
+            Event::AlarmA => rtc.cr.modify(|_, w| w.alraie().clear_bit()),
+            Event::AlarmB => rtc.cr.modify(|_, w| w.alrbie().clear_bit()),
+            Event::Wakeup => rtc.cr.modify(|_, w| w.wutie().clear_bit()),
+        });
+
+        // Both alarms share one EXTI line, only mask it if neither is in use
+        let cr = self.rtc.cr.read();
+        let line_in_use = match event {
+            Event::AlarmA | Event::AlarmB => cr.alraie().bit_is_set() || cr.alrbie().bit_is_set(),
+            Event::Wakeup => false,
+        };
+        if !line_in_use {
+            let line = 1 << Self::exti_line(event);
+            // NOTE(unsafe) only the bit of the EXTI line of this event is modified
+            exti.imr1.modify(|r, w| unsafe { w.bits(r.bits() & !line) });
+        }
+    }
+
+    /// Returns true, if the flag of the given event is set
+    pub fn is_pending(&self, event: Event) -> bool {
+        let isr = self.rtc.isr.read();
+        match event {
+            Event::AlarmA => isr.alraf().bit_is_set(),
+            Event::AlarmB => isr.alrbf().bit_is_set(),
+            Event::Wakeup => isr.wutf().bit_is_set(),
+        }
+    }
+
+    /// Clears the flag of the given event and its EXTI pending bit
+    pub fn clear_pending(&mut self, event: Event) {
+        // ALRAF, ALRBF and WUTF
+        let flag = match event {
+            Event::AlarmA => 1 << 8,
+            Event::AlarmB => 1 << 9,
+            Event::Wakeup => 1 << 10,
+        };
+        // INIT is only set by `modify`
+        write_isr(&self.rtc, false, flag);
+
+        // NOTE(unsafe) atomic write to a stateless register,
+        // writing zeros to the other lines has no effect
+        unsafe {
+            (*EXTI::ptr())
+                .pr1
+                .write(|w| w.bits(1 << Self::exti_line(event)))
+        };
+    }
+
+    /// Gives access to the backup registers
+    pub fn backup_registers(&mut self) -> &mut [BackupRegister; BKP_COUNT] {
+        // NOTE(unsafe) the backup registers are owned by this struct and are
+        // layed out as a contiguous array of 32 bit registers.
+        unsafe {
+            &mut *((RTC::ptr() as *const u8).add(BKP_OFFSET) as *mut [BackupRegister; BKP_COUNT])
+        }
+    }
+
+    /// Releases the RTC peripheral
+    ///
+    /// The RTC keeps running.
+    pub fn free(self) -> RTC {
+        self.rtc
+    }
+
+    fn exti_line(event: Event) -> u32 {
+        match event {
+            Event::AlarmA | Event::AlarmB => EXTI_LINE_ALARM,
+            Event::Wakeup => EXTI_LINE_WAKEUP,
+        }
+    }
+
+    /// Executes `f` with disabled write protection in initialization mode
+    ///
+    /// The calendar is stopped while `f` runs, so this is only used to write
+    /// the date, the time and the prescalers.
+    fn modify<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&mut RTC),
+    {
+        self.unprotect(|rtc| {
+            // Enter the initialization mode
+            if rtc.isr.read().initf().bit_is_clear() {
+                write_isr(rtc, true, 0);
+                while rtc.isr.read().initf().bit_is_clear() {}
+            }
+
+            f(rtc);
+
+            // Exit the initialization mode, clear RSF and wait for the shadow registers to be
+            // synchronized
+            write_isr(rtc, false, 1 << 5);
+            while rtc.isr.read().rsf().bit_is_clear() {}
+        });
+    }
+
+    /// Executes `f` with disabled write protection
+    ///
+    /// The calendar keeps running.
+    fn unprotect<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&mut RTC),
+    {
+        // Disable the write protection
+        // NOTE(unsafe) writes the documented key sequence
+        self.rtc.wpr.write(|w| unsafe { w.bits(0xCA) });
+        self.rtc.wpr.write(|w| unsafe { w.bits(0x53) });
+
+        f(&mut self.rtc);
+
+        // Enable the write protection again
+        // NOTE(unsafe) any wrong key activates the write protection
+        self.rtc.wpr.write(|w| unsafe { w.bits(0xFF) });
+    }
+}
+
+/// Splits a value in the range 0..=99 into its tens and units
+fn bcd2_encode(value: u32) -> (u32, u32) {
+    (value / 10, value % 10)
+}
+
+/// Joins tens and units into a value
+fn bcd2_decode(tens: u32, units: u32) -> u32 {
+    tens * 10 + units
+}
+
+/// Encodes a time of the day into the layout of the TR register
+fn time_bits(hours: u8, minutes: u8, seconds: u8) -> u32 {
+    let (ht, hu) = bcd2_encode(u32::from(hours));
+    let (mnt, mnu) = bcd2_encode(u32::from(minutes));
+    let (st, su) = bcd2_encode(u32::from(seconds));
+
+    (ht << 20) | (hu << 16) | (mnt << 12) | (mnu << 8) | (st << 4) | su
+}
+
+/// Writes INIT and clears the given flags of ISR
+///
+/// The other rc_w0 flags are written with 1, which leaves them unchanged, so
+/// that no flag, which is set between a read and the write, gets lost.
+fn write_isr(rtc: &RTC, init: bool, clear: u32) {
+    // NOTE(unsafe) INIT is the only other writable bit of ISR
+    rtc.isr
+        .write(|w| unsafe { w.bits((ISR_RC_W0 & !clear) | (u32::from(init) << 7)) });
+}
@@ -0,0 +1,713 @@
+//! Sigma-Delta Analog to Digital Converters (SDADC)
+//!
+//! The STM32F373 and STM32F378 have three 16 bit SDADCs. Each SDADC has
+//! three configurations (gain, input mode and common mode), which are
+//! assigned to the channels. Every channel can be converted either in the
+//! injected group or as the single regular channel.
+//!
+//! ```
+//! let mut sdadc1 = sdadc::SdAdc::sdadc1(dp.SDADC1, &mut rcc.apb1, &mut rcc.apb2, &mut dp.PWR, clocks);
+//! sdadc1.set_reference(Reference::Internal1V8);
+//! sdadc1.configure(Conf::Conf0, Config::default().gain(Gain::X2));
+//! sdadc1.assign(&pe12, Conf::Conf0);
+//! sdadc1.calibrate();
+//!
+//! sdadc1.start_regular(&pe12, true);
+//! let value: i16 = block!(sdadc1.read_regular()).unwrap();
+//! ```
+use core::marker::PhantomData;
+
+use embedded_hal::adc::Channel;
+
+use crate::{
+    dma,
+    gpio::{gpiob, gpiod, gpioe, Analog},
+    pac::{PWR, RCC, SDADC1, SDADC2, SDADC3},
+    rcc::{Clocks, APB1, APB2},
+};
+
+/// Maximum frequency of the SDADC clock in fast mode
+const MAX_SDADC_CLK_HZ: u32 = 6_000_000;
+
+/// SDADC error
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// A result was overwritten before it was read
+    Overrun,
+}
+
+/// Reference voltage of all SDADCs
+///
+/// The reference is selected in SDADC1 and shared by all SDADCs.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Reference {
+    /// External reference voltage on the VREFSD+ pin
+    External,
+    /// Internal 1.2 V reference voltage
+    Internal1V2,
+    /// Internal 1.8 V reference voltage
+    Internal1V8,
+    /// The analog supply voltage VDDSD
+    Vdd,
+}
+
+/// Gain of a configuration
+#[derive(Clone, Copy, PartialEq)]
+pub enum Gain {
+    /// Gain 0.5
+    X0_5,
+    /// Gain 1
+    X1,
+    /// Gain 2
+    X2,
+    /// Gain 4
+    X4,
+    /// Gain 8
+    X8,
+    /// Gain 16
+    X16,
+    /// Gain 32
+    X32,
+}
+
+/// Input mode of a configuration
+#[derive(Clone, Copy, PartialEq)]
+pub enum InputMode {
+    /// The channel is converted against its negative input AINxM
+    Differential,
+    /// Single ended input, the result is offset by half the range
+    SingleEndedOffset,
+    /// Single ended input against the zero-volt reference
+    SingleEndedZeroReference,
+}
+
+/// Common mode of a configuration
+#[derive(Clone, Copy, PartialEq)]
+pub enum CommonMode {
+    /// VSSSD
+    Vss,
+    /// VDDSD / 2
+    HalfVdd,
+    /// VDDSD
+    Vdd,
+}
+
+/// One of the three configurations of an SDADC
+#[derive(Clone, Copy, PartialEq)]
+pub enum Conf {
+    /// Configuration 0
+    Conf0,
+    /// Configuration 1
+    Conf1,
+    /// Configuration 2
+    Conf2,
+}
+
+/// Settings of a configuration
+///
+/// The default is gain 1, differential inputs, VSSSD as common mode and
+/// no offset, which is also the reset value.
+#[derive(Clone, Copy)]
+pub struct Config {
+    gain: Gain,
+    input_mode: InputMode,
+    common_mode: CommonMode,
+    offset: u16,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            gain: Gain::X1,
+            input_mode: InputMode::Differential,
+            common_mode: CommonMode::Vss,
+            offset: 0,
+        }
+    }
+}
+
+impl Config {
+    /// Sets the gain
+    pub fn gain(mut self, gain: Gain) -> Self {
+        self.gain = gain;
+        self
+    }
+
+    /// Sets the input mode
+    pub fn input_mode(mut self, input_mode: InputMode) -> Self {
+        self.input_mode = input_mode;
+        self
+    }
+
+    /// Sets the common mode
+    pub fn common_mode(mut self, common_mode: CommonMode) -> Self {
+        self.common_mode = common_mode;
+        self
+    }
+
+    /// Sets the 12 bit offset, which is subtracted from every result
+    ///
+    /// The offset is overwritten by [`calibrate`](struct.SdAdc.html#method.calibrate).
+    pub fn offset(mut self, offset: u16) -> Self {
+        self.offset = offset & 0xFFF;
+        self
+    }
+
+    /// Conversion to bits for CONFxR
+    fn bits(self) -> u32 {
+        let gain = match self.gain {
+            Gain::X1 => 0b000,
+            Gain::X2 => 0b001,
+            Gain::X4 => 0b010,
+            Gain::X8 => 0b011,
+            Gain::X16 => 0b100,
+            Gain::X32 => 0b101,
+            Gain::X0_5 => 0b111,
+        };
+        let se = match self.input_mode {
+            InputMode::Differential => 0b00,
+            InputMode::SingleEndedOffset => 0b01,
+            InputMode::SingleEndedZeroReference => 0b11,
+        };
+        let common = match self.common_mode {
+            CommonMode::Vss => 0b00,
+            CommonMode::HalfVdd => 0b01,
+            CommonMode::Vdd => 0b10,
+        };
+        (common << 30) | (se << 26) | (gain << 20) | u32::from(self.offset)
+    }
+}
+
+/// SDADC interrupt events
+#[derive(Clone, Copy, PartialEq)]
+pub enum Event {
+    /// The calibration has been completed
+    EndOfCalibration,
+    /// An injected conversion has been completed
+    InjectedEndOfConversion,
+    /// An injected result has been overwritten before it was read
+    InjectedOverrun,
+    /// A regular conversion has been completed
+    RegularEndOfConversion,
+    /// A regular result has been overwritten before it was read
+    RegularOverrun,
+}
+
+impl Event {
+    /// Bit in the CR1 and ISR registers
+    fn mask(self) -> u32 {
+        match self {
+            Event::EndOfCalibration => 1 << 0,
+            Event::InjectedEndOfConversion => 1 << 1,
+            Event::InjectedOverrun => 1 << 2,
+            Event::RegularEndOfConversion => 1 << 3,
+            Event::RegularOverrun => 1 << 4,
+        }
+    }
+}
+
+/// A set of channels of the injected group of an SDADC
+///
+/// The channels are converted in ascending order, starting with the lowest
+/// channel number.
+pub struct InjectedChannels<SDADC> {
+    channels: u32,
+    _sdadc: PhantomData<SDADC>,
+}
+
+impl<SDADC> InjectedChannels<SDADC> {
+    /// Creates an empty set of channels
+    pub fn new() -> Self {
+        InjectedChannels {
+            channels: 0,
+            _sdadc: PhantomData,
+        }
+    }
+
+    /// Adds a channel to the injected group
+    pub fn add<PIN>(mut self, _pin: &PIN) -> Self
+    where
+        PIN: Channel<SDADC, ID = u8>,
+    {
+        self.channels |= 1 << PIN::channel();
+        self
+    }
+
+    /// Returns true, if no channel has been added yet
+    pub fn is_empty(&self) -> bool {
+        self.channels == 0
+    }
+}
+
+impl<SDADC> Default for InjectedChannels<SDADC> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A sigma-delta ADC
+pub struct SdAdc<SDADC> {
+    rb: SDADC,
+}
+
+/// Maps pins to the positive inputs AINxP of the SDADC channels.
+///
+/// In differential mode, the negative input AINxM has to be in analog mode as well.
+macro_rules! sdadc_pins {
+    ($SDADC:ident, $($pin:ty => $chan:expr),+ $(,)*) => {
+        $(
+            impl Channel<$SDADC> for $pin {
+                type ID = u8;
+
+                fn channel() -> u8 { $chan }
+            }
+        )+
+    };
+}
+
+sdadc_pins!(SDADC1,
+    gpioe::PE12<Analog> => 0,
+    gpioe::PE11<Analog> => 1,
+    gpioe::PE10<Analog> => 2,
+    gpioe::PE7<Analog> => 3,
+    gpiob::PB2<Analog> => 4,
+    gpiob::PB1<Analog> => 5,
+    gpiob::PB0<Analog> => 6,
+    gpioe::PE9<Analog> => 7,
+    gpioe::PE8<Analog> => 8,
+);
+
+sdadc_pins!(SDADC2,
+    gpioe::PE7<Analog> => 5,
+    gpiob::PB2<Analog> => 6,
+    gpioe::PE9<Analog> => 7,
+    gpioe::PE8<Analog> => 8,
+);
+
+sdadc_pins!(SDADC3,
+    gpiod::PD14<Analog> => 0,
+    gpiod::PD13<Analog> => 1,
+    gpiod::PD12<Analog> => 2,
+    gpiod::PD11<Analog> => 3,
+    gpiod::PD10<Analog> => 4,
+    gpiod::PD9<Analog> => 5,
+    gpiod::PD8<Analog> => 6,
+    gpiob::PB15<Analog> => 7,
+    gpiob::PB14<Analog> => 8,
+);
+
+macro_rules! sdadc_hal {
+    ($(
+        $SDADC:ident: ($sdadcx:ident, $sdadcXen:ident, $sdadcXrst:ident, $pwr_en:expr),
+    )+) => {
+        $(
+            impl SdAdc<$SDADC> {
+                /// Init a new SDADC
+                ///
+                /// Powers the analog part of the SDADC, enables the clock and
+                /// enables the SDADC. The SDADC clock is derived from SYSCLK with
+                /// the smallest prescaler, which keeps it below 6 MHz.
+                ///
+                /// # Panics
+                /// If SYSCLK is too fast for the SDADC clock to be below 6 MHz.
+                pub fn $sdadcx(
+                    rb: $SDADC,
+                    apb1: &mut APB1,
+                    apb2: &mut APB2,
+                    pwr: &mut PWR,
+                    clocks: Clocks,
+                ) -> Self {
+                    set_prescaler(clocks);
+
+                    apb1.enr().modify(|_, w| w.pwren().set_bit());
+                    // NOTE(unsafe) only the power enable bit of this SDADC is set
+                    pwr.cr.modify(|r, w| unsafe { w.bits(r.bits() | $pwr_en) });
+
+                    apb2.enr().modify(|_, w| w.$sdadcXen().set_bit());
+                    apb2.rstr().modify(|_, w| w.$sdadcXrst().set_bit());
+                    apb2.rstr().modify(|_, w| w.$sdadcXrst().clear_bit());
+
+                    let sdadc = SdAdc { rb };
+                    // ADON, then wait for the stabilization, STABIP is cleared
+                    // when the SDADC is stable, which may happen before it is
+                    // polled the first time
+                    // NOTE(unsafe) CR2 is in its reset state
+                    sdadc.rb.cr2.write(|w| unsafe { w.bits(1) });
+                    while sdadc.rb.isr.read().bits() & (1 << 15) != 0 {}
+
+                    sdadc
+                }
+
+                /// Writes a configuration
+                pub fn configure(&mut self, conf: Conf, config: Config) {
+                    let bits = config.bits();
+                    self.modify_in_init_mode(|rb| unsafe {
+                        // NOTE(unsafe) the configuration covers all used bits
+                        match conf {
+                            Conf::Conf0 => rb.conf0r.write(|w| w.bits(bits)),
+                            Conf::Conf1 => rb.conf1r.write(|w| w.bits(bits)),
+                            Conf::Conf2 => rb.conf2r.write(|w| w.bits(bits)),
+                        }
+                    });
+                }
+
+                /// Assigns a configuration to a channel
+                pub fn assign<PIN>(&mut self, _pin: &PIN, conf: Conf)
+                where
+                    PIN: Channel<$SDADC, ID = u8>,
+                {
+                    let chan = u32::from(PIN::channel());
+                    let bits = conf as u32;
+                    self.modify_in_init_mode(|rb| unsafe {
+                        // NOTE(unsafe) only the CONFCHx field of the channel is modified
+                        if chan < 8 {
+                            rb.confchr1.modify(|r, w| {
+                                w.bits((r.bits() & !(0b11 << (4 * chan))) | (bits << (4 * chan)))
+                            });
+                        } else {
+                            rb.confchr2.modify(|r, w| w.bits((r.bits() & !0b11) | bits));
+                        }
+                    });
+                }
+
+                /// Calibrates the offsets of all three configurations
+                ///
+                /// This has to be repeated, whenever a configuration is changed.
+                pub fn calibrate(&mut self) {
+                    // CALIBCNT: calibrate configurations 0, 1 and 2
+                    self.modify_in_init_mode(|rb| unsafe {
+                        // NOTE(unsafe) only CALIBCNT is modified
+                        rb.cr2.modify(|r, w| w.bits((r.bits() & !(0b11 << 1)) | (0b10 << 1)))
+                    });
+                    // STARTCALIB
+                    // NOTE(unsafe) STARTCALIB is cleared by hardware
+                    self.rb.cr2.modify(|r, w| unsafe { w.bits(r.bits() | (1 << 4)) });
+                    while self.rb.isr.read().bits() & Event::EndOfCalibration.mask() == 0 {}
+                    self.clear_pending(Event::EndOfCalibration);
+                }
+
+                /// Sets the channels of the injected group
+                ///
+                /// # Panics
+                ///
+                /// Panics if the set of channels is empty.
+                pub fn set_injected_channels(&mut self, channels: &InjectedChannels<$SDADC>) {
+                    assert!(!channels.is_empty(), "the injected group needs at least one channel");
+                    let bits = channels.channels;
+                    // NOTE(unsafe) every channel is the x of AINxP of a mapped pin
+                    self.modify_in_init_mode(|rb| unsafe { rb.jchgr.write(|w| w.bits(bits)) });
+                }
+
+                /// Starts the conversion of the injected group
+                ///
+                /// In continuous mode, the group is converted over and over again,
+                /// until [`stop_injected`](#method.stop_injected) is called.
+                pub fn start_injected(&mut self, continuous: bool) {
+                    // JCONT, then JSWSTART
+                    self.modify_cr2(1 << 5, u32::from(continuous) << 5);
+                    self.modify_cr2(1 << 15, 1 << 15);
+                }
+
+                /// Stops continuous conversions of the injected group
+                ///
+                /// The ongoing conversion of the group is finished.
+                pub fn stop_injected(&mut self) {
+                    self.modify_cr2(1 << 5, 0);
+                }
+
+                /// Returns the next result of the injected group with its channel number
+                pub fn read_injected(&mut self) -> nb::Result<(u8, i16), Error> {
+                    let isr = self.rb.isr.read().bits();
+                    if isr & Event::InjectedOverrun.mask() != 0 {
+                        self.clear_pending(Event::InjectedOverrun);
+                        Err(nb::Error::Other(Error::Overrun))
+                    } else if isr & Event::InjectedEndOfConversion.mask() != 0 {
+                        // Reading JDATAR clears JEOCF
+                        let jdatar = self.rb.jdatar.read().bits();
+                        Ok(((jdatar >> 24 & 0xF) as u8, jdatar as u16 as i16))
+                    } else {
+                        Err(nb::Error::WouldBlock)
+                    }
+                }
+
+                /// Starts the conversion of a regular channel
+                ///
+                /// In continuous mode, the channel is converted over and over
+                /// again, until [`stop_regular`](#method.stop_regular) is called.
+                /// Regular conversions are interrupted by injected conversions.
+                pub fn start_regular<PIN>(&mut self, _pin: &PIN, continuous: bool)
+                where
+                    PIN: Channel<$SDADC, ID = u8>,
+                {
+                    // RCH and RCONT, then RSWSTART
+                    let bits = (u32::from(continuous) << 22) | (u32::from(PIN::channel()) << 16);
+                    self.modify_cr2((1 << 22) | (0b1111 << 16), bits);
+                    self.modify_cr2(1 << 23, 1 << 23);
+                }
+
+                /// Stops continuous conversions of the regular channel
+                pub fn stop_regular(&mut self) {
+                    self.modify_cr2(1 << 22, 0);
+                }
+
+                /// Selects the regular channel without starting a conversion
+                ///
+                /// This selects the channel of an SDADC, which is synchronized
+                /// with SDADC1 by [`set_regular_sync`](#method.set_regular_sync).
+                pub fn select_regular<PIN>(&mut self, _pin: &PIN)
+                where
+                    PIN: Channel<$SDADC, ID = u8>,
+                {
+                    // RCH
+                    self.modify_cr2(0b1111 << 16, u32::from(PIN::channel()) << 16);
+                }
+
+                /// Returns the next result of the regular channel
+                pub fn read_regular(&mut self) -> nb::Result<i16, Error> {
+                    let isr = self.rb.isr.read().bits();
+                    if isr & Event::RegularOverrun.mask() != 0 {
+                        self.clear_pending(Event::RegularOverrun);
+                        Err(nb::Error::Other(Error::Overrun))
+                    } else if isr & Event::RegularEndOfConversion.mask() != 0 {
+                        // Reading RDATAR clears REOCF
+                        Ok(self.rb.rdatar.read().bits() as u16 as i16)
+                    } else {
+                        Err(nb::Error::WouldBlock)
+                    }
+                }
+
+                /// Converts a regular channel continuously and stores the results
+                /// in the buffer using DMA
+                ///
+                /// The conversion is started right away. The results are the bits
+                /// of signed values, which are recovered with `as i16`.
+                pub fn read_dma<PIN, B, C>(
+                    mut self,
+                    _pin: &PIN,
+                    buffer: B,
+                    mut channel: C,
+                ) -> dma::Transfer<B, C, Self>
+                where
+                    PIN: Channel<$SDADC, ID = u8>,
+                    Self: dma::OnChannel<C>,
+                    B: dma::WriteBuffer<Word = u16> + 'static,
+                    C: dma::Channel,
+                {
+                    // RCH and RCONT, RSWSTART is set once DMA is enabled
+                    let bits = (1 << 22) | (u32::from(PIN::channel()) << 16);
+                    self.modify_cr2((1 << 22) | (0b1111 << 16), bits);
+
+                    // NOTE(unsafe) taking the address of a register
+                    let pa = unsafe { &(*$SDADC::ptr()).rdatar } as *const _ as u32;
+                    channel.set_peripheral_address(pa, dma::Increment::Disable);
+
+                    dma::Transfer::start_write(buffer, channel, self)
+                }
+
+                /// Starts listening for an interrupt event
+                pub fn listen(&mut self, event: Event) {
+                    // The interrupt enable bits can be written outside of the
+                    // initialization mode, so running conversions continue
+                    // NOTE(unsafe) only the enable bit of the event is modified
+                    self.rb.cr1.modify(|r, w| unsafe { w.bits(r.bits() | event.mask()) });
+                }
+
+                /// Stops listening for an interrupt event
+                pub fn unlisten(&mut self, event: Event) {
+                    // NOTE(unsafe) only the enable bit of the event is modified
+                    self.rb.cr1.modify(|r, w| unsafe { w.bits(r.bits() & !event.mask()) });
+                }
+
+                /// Returns true, if the event has occurred
+                pub fn is_pending(&self, event: Event) -> bool {
+                    self.rb.isr.read().bits() & event.mask() != 0
+                }
+
+                /// Clears the flag of the event
+                ///
+                /// The end of conversion flags are cleared by reading the result.
+                pub fn clear_pending(&mut self, event: Event) {
+                    // NOTE(unsafe) the flags are cleared by writing 1, zeros have no effect
+                    self.rb.clrisr.write(|w| unsafe { w.bits(event.mask()) });
+                }
+
+                /// Releases the SDADC peripheral
+                ///
+                /// The SDADC is disabled beforehand.
+                pub fn free(self) -> $SDADC {
+                    // NOTE(unsafe) clearing ADON disables the SDADC
+                    self.rb.cr2.modify(|r, w| unsafe { w.bits(r.bits() & !1) });
+                    self.rb
+                }
+
+                fn modify_cr2(&mut self, mask: u32, bits: u32) {
+                    // NOTE(unsafe) STARTCALIB, JSWSTART and RSWSTART are always
+                    // written as 0, unless they are in the mask
+                    let triggers = (1 << 4) | (1 << 15) | (1 << 23);
+                    self.rb.cr2.modify(|r, w| unsafe {
+                        w.bits((r.bits() & !(mask | triggers)) | bits)
+                    });
+                }
+
+                /// Runs `f` in initialization mode, in which the configuration
+                /// registers can be written
+                fn modify_in_init_mode<F>(&mut self, f: F)
+                where
+                    F: FnOnce(&$SDADC),
+                {
+                    // INIT
+                    // NOTE(unsafe) only INIT is modified
+                    self.rb.cr1.modify(|r, w| unsafe { w.bits(r.bits() | (1 << 31)) });
+                    while self.rb.isr.read().bits() & (1 << 31) == 0 {}
+                    f(&self.rb);
+                    self.rb.cr1.modify(|r, w| unsafe { w.bits(r.bits() & !(1 << 31)) });
+                }
+            }
+
+            impl dma::Target for SdAdc<$SDADC> {
+                fn enable_dma(&mut self) {
+                    // RDMAEN, then RSWSTART
+                    // NOTE(unsafe) only RDMAEN is modified
+                    self.modify_in_init_mode(|rb| unsafe {
+                        rb.cr1.modify(|r, w| w.bits(r.bits() | (1 << 17)))
+                    });
+                    self.modify_cr2(1 << 23, 1 << 23);
+                }
+
+                fn disable_dma(&mut self) {
+                    self.stop_regular();
+                    // NOTE(unsafe) only RDMAEN is modified
+                    self.modify_in_init_mode(|rb| unsafe {
+                        rb.cr1.modify(|r, w| w.bits(r.bits() & !(1 << 17)))
+                    });
+                }
+            }
+        )+
+    };
+}
+
+sdadc_hal! {
+    SDADC1: (sdadc1, sdadc1en, sdadc1rst, 1 << 9),
+    SDADC2: (sdadc2, sdadc2en, sdadc2rst, 1 << 10),
+    SDADC3: (sdadc3, sdadc3en, sdadc3rst, 1 << 11),
+}
+
+impl SdAdc<SDADC1> {
+    /// Selects the reference voltage of all SDADCs
+    pub fn set_reference(&mut self, reference: Reference) {
+        let bits = match reference {
+            Reference::External => 0b00,
+            Reference::Internal1V2 => 0b01,
+            Reference::Internal1V8 => 0b10,
+            Reference::Vdd => 0b11,
+        };
+        // NOTE(unsafe) only REFV is modified
+        self.modify_in_init_mode(|rb| unsafe {
+            rb.cr1
+                .modify(|r, w| w.bits((r.bits() & !(0b11 << 8)) | (bits << 8)))
+        });
+    }
+
+    /// Returns the next results of synchronized injected conversions of
+    /// SDADC1, SDADC2 and SDADC3
+    ///
+    /// SDADC2 and SDADC3 have to be synchronized with
+    /// [`set_injected_sync`](struct.SdAdc.html#method.set_injected_sync).
+    pub fn read_injected_sync(&mut self) -> nb::Result<[i16; 3], Error> {
+        let isr = self.rb.isr.read().bits();
+        if isr & Event::InjectedOverrun.mask() != 0 {
+            self.clear_pending(Event::InjectedOverrun);
+            Err(nb::Error::Other(Error::Overrun))
+        } else if isr & Event::InjectedEndOfConversion.mask() != 0 {
+            // Reading the combined data registers clears the JEOCF flags
+            let jdata12 = self.rb.jdata12r.read().bits();
+            let jdata13 = self.rb.jdata13r.read().bits();
+            Ok([
+                jdata12 as u16 as i16,
+                (jdata12 >> 16) as u16 as i16,
+                (jdata13 >> 16) as u16 as i16,
+            ])
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    /// Returns the next results of synchronized regular conversions of
+    /// SDADC1, SDADC2 and SDADC3
+    ///
+    /// SDADC2 and SDADC3 have to be synchronized with
+    /// [`set_regular_sync`](struct.SdAdc.html#method.set_regular_sync).
+    pub fn read_regular_sync(&mut self) -> nb::Result<[i16; 3], Error> {
+        let isr = self.rb.isr.read().bits();
+        if isr & Event::RegularOverrun.mask() != 0 {
+            self.clear_pending(Event::RegularOverrun);
+            Err(nb::Error::Other(Error::Overrun))
+        } else if isr & Event::RegularEndOfConversion.mask() != 0 {
+            // Reading the combined data registers clears the REOCF flags
+            let rdata12 = self.rb.rdata12r.read().bits();
+            let rdata13 = self.rb.rdata13r.read().bits();
+            Ok([
+                rdata12 as u16 as i16,
+                (rdata12 >> 16) as u16 as i16,
+                (rdata13 >> 16) as u16 as i16,
+            ])
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+}
+
+macro_rules! sdadc_sync {
+    ($($SDADC:ident,)+) => {
+        $(
+            impl SdAdc<$SDADC> {
+                /// Starts the injected conversions together with SDADC1
+                ///
+                /// The injected group is started by
+                /// [`start_injected`](#method.start_injected) of SDADC1 then.
+                pub fn set_injected_sync(&mut self, sync: bool) {
+                    // NOTE(unsafe) only JSYNC is modified
+                    self.modify_in_init_mode(|rb| unsafe {
+                        rb.cr1.modify(|r, w| w.bits((r.bits() & !(1 << 14)) | (u32::from(sync) << 14)))
+                    });
+                }
+
+                /// Starts the regular conversions together with SDADC1
+                ///
+                /// The regular channel is started by
+                /// [`start_regular`](#method.start_regular) of SDADC1 then, but
+                /// the channel of this SDADC has to be selected with
+                /// [`select_regular`](#method.select_regular) beforehand.
+                pub fn set_regular_sync(&mut self, sync: bool) {
+                    // NOTE(unsafe) only RSYNC is modified
+                    self.modify_in_init_mode(|rb| unsafe {
+                        rb.cr1.modify(|r, w| w.bits((r.bits() & !(1 << 15)) | (u32::from(sync) << 15)))
+                    });
+                }
+            }
+        )+
+    };
+}
+
+sdadc_sync!(SDADC2, SDADC3,);
+
+/// Sets the prescaler of the SDADC clock, which is shared by all SDADCs
+fn set_prescaler(clocks: Clocks) {
+    // SDPRE divides SYSCLK by 2, 4, 6, 8, 10, 12, 14, 16, 20, 24, ..., 48
+    const DIVIDERS: [u32; 16] = [2, 4, 6, 8, 10, 12, 14, 16, 20, 24, 28, 32, 36, 40, 44, 48];
+    let sysclk = clocks.sysclk().0;
+    let sdpre = match DIVIDERS
+        .iter()
+        .position(|div| sysclk / div <= MAX_SDADC_CLK_HZ)
+    {
+        Some(position) => 0b1_0000 | position as u32,
+        None => panic!("SYSCLK is too fast for the SDADC clock"),
+    };
+    // NOTE(unsafe) SDPRE is only used by the SDADCs, which all use the same prescaler
+    unsafe {
+        (*RCC::ptr())
+            .cfgr
+            .modify(|r, w| w.bits((r.bits() & !(0b1_1111 << 27)) | (sdpre << 27)));
+    }
+}
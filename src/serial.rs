@@ -7,7 +7,7 @@ use crate::{
     rcc::{Clocks, APB1, APB2},
     time::Bps,
 };
-use core::{convert::Infallible, marker::PhantomData, ptr};
+use core::{convert::Infallible, marker::PhantomData};
 
 #[cfg(any(
     feature = "stm32f302",
@@ -36,10 +36,21 @@ use crate::gpio::gpiod;
     feature = "stm32f398"
 ))]
 use crate::gpio::gpioe;
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398"
+))]
+use crate::{
+    gpio::AF5,
+    pac::{UART4, UART5},
+};
 
-#[cfg(feature = "stm32f303")]
 use crate::dma;
-#[cfg(feature = "stm32f303")]
 use cortex_m::interrupt;
 
 /// Interrupt event
@@ -64,6 +75,243 @@ pub enum Error {
     Parity,
 }
 
+/// Invalid serial configuration
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ConfigError {
+    /// The baud rate can not be reached with the USART clock and the
+    /// oversampling
+    BaudRate,
+    /// 9 data bits do not leave room for a parity bit
+    WordLength,
+    /// 0.5 and 1.5 stop bits are not available on UART4 and UART5
+    StopBits,
+}
+
+/// Number of data bits of a frame, without the parity bit
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DataBits {
+    /// 7 data bits
+    Seven,
+    /// 8 data bits
+    Eight,
+    /// 9 data bits
+    ///
+    /// The `Read<u16>` and `Write<u16>` implementations exchange all 9 bits,
+    /// while the `u8` implementations drop the most significant one.
+    Nine,
+}
+
+/// Parity bit
+///
+/// The parity bit is sent after the data bits. A received parity bit is
+/// checked and removed from the data, which is read.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Parity {
+    /// No parity bit
+    None,
+    /// Even parity
+    Even,
+    /// Odd parity
+    Odd,
+}
+
+/// Number of stop bits
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StopBits {
+    /// 0.5 stop bits
+    Half,
+    /// 1 stop bit
+    One,
+    /// 1.5 stop bits
+    OneAndHalf,
+    /// 2 stop bits
+    Two,
+}
+
+/// Number of samples per bit
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Oversampling {
+    /// 16 samples per bit, more tolerant to clock deviations
+    By16,
+    /// 8 samples per bit, allows twice the baud rate
+    By8,
+}
+
+/// Configuration of a serial interface
+///
+/// The default is 8 data bits, no parity and 1 stop bit, sent LSB first with
+/// 16 times oversampling and without inversion.
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    baud_rate: Bps,
+    data_bits: DataBits,
+    parity: Parity,
+    stop_bits: StopBits,
+    oversampling: Oversampling,
+    msb_first: bool,
+    invert_data: bool,
+    invert_tx: bool,
+    invert_rx: bool,
+    swap_pins: bool,
+}
+
+impl Config {
+    /// Creates a configuration for the baud rate
+    pub fn new(baud_rate: Bps) -> Self {
+        Config {
+            baud_rate,
+            data_bits: DataBits::Eight,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+            oversampling: Oversampling::By16,
+            msb_first: false,
+            invert_data: false,
+            invert_tx: false,
+            invert_rx: false,
+            swap_pins: false,
+        }
+    }
+
+    /// Sets the baud rate
+    pub fn baud_rate(mut self, baud_rate: Bps) -> Self {
+        self.baud_rate = baud_rate;
+        self
+    }
+
+    /// Sets the number of data bits
+    pub fn data_bits(mut self, data_bits: DataBits) -> Self {
+        self.data_bits = data_bits;
+        self
+    }
+
+    /// Sets the parity
+    pub fn parity(mut self, parity: Parity) -> Self {
+        self.parity = parity;
+        self
+    }
+
+    /// Sets the number of stop bits
+    pub fn stop_bits(mut self, stop_bits: StopBits) -> Self {
+        self.stop_bits = stop_bits;
+        self
+    }
+
+    /// Sets the oversampling
+    pub fn oversampling(mut self, oversampling: Oversampling) -> Self {
+        self.oversampling = oversampling;
+        self
+    }
+
+    /// Sends and receives the most significant data bit first
+    pub fn msb_first(mut self, enable: bool) -> Self {
+        self.msb_first = enable;
+        self
+    }
+
+    /// Inverts the data bits, including the parity bit
+    pub fn invert_data(mut self, enable: bool) -> Self {
+        self.invert_data = enable;
+        self
+    }
+
+    /// Inverts the levels of the TX pin, so that it idles low
+    pub fn invert_tx(mut self, enable: bool) -> Self {
+        self.invert_tx = enable;
+        self
+    }
+
+    /// Inverts the levels of the RX pin, so that it idles low
+    pub fn invert_rx(mut self, enable: bool) -> Self {
+        self.invert_rx = enable;
+        self
+    }
+
+    /// Swaps the functions of the TX and RX pins
+    ///
+    /// The pins keep their types, so the TX pin receives and the RX pin
+    /// transmits.
+    pub fn swap_pins(mut self, enable: bool) -> Self {
+        self.swap_pins = enable;
+        self
+    }
+
+    /// Computes BRR, CR1 and CR2 for the USART clock
+    ///
+    /// CR1 enables the USART, its receiver and its transmitter.
+    fn registers(
+        &self,
+        usartclk: u32,
+        fractional_stop_bits: bool,
+    ) -> Result<(u32, u32, u32), ConfigError> {
+        let baud_rate = self.baud_rate.0;
+        if baud_rate == 0 {
+            return Err(ConfigError::BaudRate);
+        }
+        // USARTDIV, rounded to the nearest divider
+        let usartdiv = match self.oversampling {
+            Oversampling::By16 => (usartclk + baud_rate / 2) / baud_rate,
+            Oversampling::By8 => (2 * usartclk + baud_rate / 2) / baud_rate,
+        };
+        if !(16..=0xFFFF).contains(&usartdiv) {
+            return Err(ConfigError::BaudRate);
+        }
+        let brr = match self.oversampling {
+            Oversampling::By16 => usartdiv,
+            // BRR[3] is cleared and BRR[2:0] is USARTDIV[3:0] shifted right
+            Oversampling::By8 => (usartdiv & !0xF) | ((usartdiv & 0xF) >> 1),
+        };
+
+        // The word length includes the parity bit
+        let parity_bit = self.parity != Parity::None;
+        // M1 and M0
+        let m = match (self.data_bits, parity_bit) {
+            (DataBits::Seven, false) => 0b10,
+            (DataBits::Seven, true) | (DataBits::Eight, false) => 0b00,
+            (DataBits::Eight, true) | (DataBits::Nine, false) => 0b01,
+            (DataBits::Nine, true) => return Err(ConfigError::WordLength),
+        };
+        // PCE and PS
+        let parity = match self.parity {
+            Parity::None => 0b00,
+            Parity::Even => 0b10,
+            Parity::Odd => 0b11,
+        };
+        let over8 = match self.oversampling {
+            Oversampling::By16 => 0,
+            Oversampling::By8 => 1,
+        };
+        // UE, RE and TE
+        let cr1 = ((m >> 1) << 28) | (over8 << 15) | ((m & 0b1) << 12) | (parity << 9) | 0b1101;
+
+        let stop = match self.stop_bits {
+            StopBits::One => 0b00,
+            StopBits::Half => 0b01,
+            StopBits::Two => 0b10,
+            StopBits::OneAndHalf => 0b11,
+        };
+        if !fractional_stop_bits
+            && (self.stop_bits == StopBits::Half || self.stop_bits == StopBits::OneAndHalf)
+        {
+            return Err(ConfigError::StopBits);
+        }
+        let cr2 = (u32::from(self.msb_first) << 19)
+            | (u32::from(self.invert_data) << 18)
+            | (u32::from(self.invert_tx) << 17)
+            | (u32::from(self.invert_rx) << 16)
+            | (u32::from(self.swap_pins) << 15)
+            | (stop << 12);
+
+        Ok((brr, cr1, cr2))
+    }
+}
+
+impl From<Bps> for Config {
+    fn from(baud_rate: Bps) -> Self {
+        Config::new(baud_rate)
+    }
+}
+
 // FIXME these should be "closed" traits
 /// TX pin - DO NOT IMPLEMENT THIS TRAIT
 pub unsafe trait TxPin<USART> {}
@@ -198,6 +446,48 @@ unsafe impl RxPin<USART3> for gpiod::PD9<AF7> {}
 ))]
 unsafe impl RxPin<USART3> for gpioe::PE15<AF7> {}
 
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398"
+))]
+unsafe impl TxPin<UART4> for gpioc::PC10<AF5> {}
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398"
+))]
+unsafe impl RxPin<UART4> for gpioc::PC11<AF5> {}
+
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398"
+))]
+unsafe impl TxPin<UART5> for gpioc::PC12<AF5> {}
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398"
+))]
+unsafe impl RxPin<UART5> for gpiod::PD2<AF5> {}
+
 /// Serial abstraction
 pub struct Serial<USART, PINS> {
     usart: USART,
@@ -214,41 +504,59 @@ pub struct Tx<USART> {
     _usart: PhantomData<USART>,
 }
 
+/// Mask of the received data bits in RDR for the word length and parity
+/// configured in CR1
+fn data_mask(cr1: u32) -> u16 {
+    // M1 and M0, the word length includes the parity bit
+    let word_length = match ((cr1 >> 28) & 0b1, (cr1 >> 12) & 0b1) {
+        (1, _) => 7,
+        (_, 1) => 9,
+        _ => 8,
+    };
+    // PCE
+    let parity_bit = (cr1 >> 10) & 0b1;
+    (1 << (word_length - parity_bit)) - 1
+}
+
 macro_rules! hal {
     ($(
-        $USARTX:ident: ($usartX:ident, $APB:ident, $usartXen:ident, $usartXrst:ident, $pclkX:ident),
+        $USARTX:ident: ($usartX:ident, $APB:ident, $usartXen:ident, $usartXrst:ident, $pclkX:ident, $fractional_stop_bits:expr),
     )+) => {
         $(
             impl<TX, RX> Serial<$USARTX, (TX, RX)> {
                 /// Configures a USART peripheral to provide serial communication
-                pub fn $usartX(
+                ///
+                /// Takes a baud rate or a `Config`. Configurations, which the
+                /// USART does not support, are rejected before it is enabled.
+                pub fn $usartX<C>(
                     usart: $USARTX,
                     pins: (TX, RX),
-                    baud_rate: Bps,
+                    config: C,
                     clocks: Clocks,
                     apb: &mut $APB,
-                ) -> Self
+                ) -> Result<Self, ConfigError>
                 where
+                    C: Into<Config>,
                     TX: TxPin<$USARTX>,
                     RX: RxPin<$USARTX>,
                 {
+                    let (brr, cr1, cr2) =
+                        config.into().registers(clocks.$pclkX().0, $fractional_stop_bits)?;
+
                     // enable or reset $USARTX
                     apb.enr().modify(|_, w| w.$usartXen().set_bit());
                     apb.rstr().modify(|_, w| w.$usartXrst().set_bit());
                     apb.rstr().modify(|_, w| w.$usartXrst().clear_bit());
 
-                    let brr = clocks.$pclkX().0 / baud_rate.0;
-                    assert!(brr >= 16, "impossible baud rate");
-                    // NOTE(write): uses all bits of this register.
+                    // NOTE(unsafe) CR2 and BRR are written while UE is cleared,
+                    // all bits are valid
+                    usart.cr2.write(|w| unsafe { w.bits(cr2) });
                     usart.brr.write(|w| unsafe { w.bits(brr) });
+                    // NOTE(unsafe) enables the USART, its receiver and its
+                    // transmitter with the word length, parity and oversampling
+                    usart.cr1.write(|w| unsafe { w.bits(cr1) });
 
-                    usart.cr1.modify(|_, w| {
-                        w.ue().enabled();  // enable USART
-                        w.re().enabled();  // enable receiver
-                        w.te().enabled()   // enable transmitter
-                    });
-
-                    Serial { usart, pins }
+                    Ok(Serial { usart, pins })
                 }
 
                 /// Starts listening for an interrupt event
@@ -297,6 +605,15 @@ macro_rules! hal {
                 type Error = Error;
 
                 fn read(&mut self) -> nb::Result<u8, Error> {
+                    serial::Read::<u16>::read(self).map(|word| word as u8)
+                }
+            }
+
+            /// Reads the data bits of a word, all 9 for frames with 9 data bits
+            impl serial::Read<u16> for Rx<$USARTX> {
+                type Error = Error;
+
+                fn read(&mut self) -> nb::Result<u16, Error> {
                     // NOTE(unsafe) atomic read with no side effects
                     let isr = unsafe { (*$USARTX::ptr()).isr.read() };
 
@@ -316,10 +633,14 @@ macro_rules! hal {
                         icr.write(|w| w.orecf().clear());
                         nb::Error::Other(Error::Overrun)
                     } else if isr.rxne().bit_is_set() {
-                        // NOTE(read_volatile) see `write_volatile` below
-                        return Ok(unsafe {
-                            ptr::read_volatile(&(*$USARTX::ptr()).rdr as *const _ as *const _)
-                        });
+                        // NOTE(unsafe) atomic reads, the word length and the
+                        // parity can only be changed by reconfiguring the USART
+                        let (cr1, rdr) = unsafe {
+                            let usart = &*$USARTX::ptr();
+                            (usart.cr1.read().bits(), usart.rdr.read().rdr().bits())
+                        };
+                        // A received parity bit follows the data bits
+                        return Ok(rdr & data_mask(cr1));
                     } else {
                         nb::Error::WouldBlock
                     })
@@ -327,6 +648,19 @@ macro_rules! hal {
             }
 
             impl serial::Write<u8> for Tx<$USARTX> {
+                type Error = Infallible;
+
+                fn flush(&mut self) -> nb::Result<(), Infallible> {
+                    serial::Write::<u16>::flush(self)
+                }
+
+                fn write(&mut self, byte: u8) -> nb::Result<(), Infallible> {
+                    serial::Write::<u16>::write(self, u16::from(byte))
+                }
+            }
+
+            /// Writes all 9 bits of a word, for frames with 9 data bits
+            impl serial::Write<u16> for Tx<$USARTX> {
                 // NOTE(Infallible) See section "29.7 USART interrupts"; the only possible errors during
                 // transmission are: clear to send (which is disabled in this case) errors and
                 // framing errors (which only occur in SmartCard mode); neither of these apply to
@@ -344,16 +678,13 @@ macro_rules! hal {
                     }
                 }
 
-                fn write(&mut self, byte: u8) -> nb::Result<(), Infallible> {
+                fn write(&mut self, word: u16) -> nb::Result<(), Infallible> {
                     // NOTE(unsafe) atomic read with no side effects
                     let isr = unsafe { (*$USARTX::ptr()).isr.read() };
 
                     if isr.txe().bit_is_set() {
                         // NOTE(unsafe) atomic write to stateless register
-                        // NOTE(write_volatile) 8-bit write that's not possible through the svd2rust API
-                        unsafe {
-                            ptr::write_volatile(&(*$USARTX::ptr()).tdr as *const _ as *mut _, byte)
-                        }
+                        unsafe { (*$USARTX::ptr()).tdr.write(|w| w.tdr().bits(word)) }
                         Ok(())
                     } else {
                         Err(nb::Error::WouldBlock)
@@ -363,7 +694,8 @@ macro_rules! hal {
 
             impl blocking::serial::write::Default<u8> for Tx<$USARTX> {}
 
-            #[cfg(feature = "stm32f303")]
+            impl blocking::serial::write::Default<u16> for Tx<$USARTX> {}
+
             impl Rx<$USARTX> {
                 /// Fill the buffer with received data using DMA.
                 pub fn read_exact<B, C>(
@@ -382,9 +714,26 @@ macro_rules! hal {
 
                     dma::Transfer::start_write(buffer, channel, self)
                 }
+
+                /// Receive data continuously into both halves of the buffer using DMA.
+                pub fn read_circular<B, C>(
+                    self,
+                    buffer: &'static mut [B; 2],
+                    mut channel: C
+                ) -> dma::CircTransfer<B, C, Self>
+                where
+                    Self: dma::OnChannel<C>,
+                    B: dma::WriteTarget<Word = u8>,
+                    C: dma::Channel,
+                {
+                    // NOTE(unsafe) taking the address of a register
+                    let pa = unsafe { &(*$USARTX::ptr()).rdr } as *const _ as u32;
+                    channel.set_peripheral_address(pa, dma::Increment::Disable);
+
+                    dma::CircTransfer::start(buffer, channel, self)
+                }
             }
 
-            #[cfg(feature = "stm32f303")]
             impl Tx<$USARTX> {
                 /// Transmit all data in the buffer using DMA.
                 pub fn write_all<B, C>(
@@ -405,7 +754,6 @@ macro_rules! hal {
                 }
             }
 
-            #[cfg(feature = "stm32f303")]
             impl dma::Target for Rx<$USARTX> {
                 fn enable_dma(&mut self) {
                     // NOTE(unsafe) critical section prevents races
@@ -424,7 +772,6 @@ macro_rules! hal {
                 }
             }
 
-            #[cfg(feature = "stm32f303")]
             impl dma::Target for Tx<$USARTX> {
                 fn enable_dma(&mut self) {
                     // NOTE(unsafe) critical section prevents races
@@ -457,13 +804,27 @@ macro_rules! hal {
     feature = "stm32f398"
 ))]
 hal! {
-    USART1: (usart1, APB2, usart1en, usart1rst, pclk2),
-    USART2: (usart2, APB1, usart2en, usart2rst, pclk1),
-    USART3: (usart3, APB1, usart3en, usart3rst, pclk1),
+    USART1: (usart1, APB2, usart1en, usart1rst, pclk2, true),
+    USART2: (usart2, APB1, usart2en, usart2rst, pclk1, true),
+    USART3: (usart3, APB1, usart3en, usart3rst, pclk1, true),
 }
 
 #[cfg(any(feature = "stm32f302", feature = "stm32f334"))]
 hal! {
-    USART1: (usart1, APB2, usart1en, usart1rst, pclk2),
-    USART2: (usart2, APB1, usart2en, usart2rst, pclk1),
+    USART1: (usart1, APB2, usart1en, usart1rst, pclk2, true),
+    USART2: (usart2, APB1, usart2en, usart2rst, pclk1, true),
+}
+
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398"
+))]
+hal! {
+    UART4: (uart4, APB1, uart4en, uart4rst, pclk1, false),
+    UART5: (uart5, APB1, uart5en, uart5rst, pclk1, false),
 }
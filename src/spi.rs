@@ -1,7 +1,8 @@
 //! Serial Peripheral Interface (SPI) bus
 
-use core::ptr;
+use core::{mem, ptr};
 
+use crate::hal::blocking::spi::Write;
 use crate::hal::spi::FullDuplex;
 pub use crate::hal::spi::{Mode, Phase, Polarity};
 use crate::pac::{
@@ -9,8 +10,43 @@ use crate::pac::{
     SPI1, SPI2, SPI3,
 };
 use crate::stm32::spi1;
+#[cfg(any(
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f398"
+))]
+use crate::{
+    gpio::gpioe::{PE11, PE12, PE13, PE14, PE2, PE4, PE5, PE6},
+    pac::SPI4,
+};
 
+use crate::dma;
+#[cfg(any(
+    feature = "stm32f301",
+    feature = "stm32f302",
+    feature = "stm32f303",
+    feature = "stm32f318",
+    feature = "stm32f328",
+    feature = "stm32f334",
+    feature = "stm32f358",
+    feature = "stm32f373",
+    feature = "stm32f378",
+    feature = "stm32f398"
+))]
+use crate::gpio::gpioa::PA4;
 use crate::gpio::gpioa::{PA5, PA6, PA7};
+#[cfg(any(
+    feature = "stm32f301",
+    feature = "stm32f302",
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f318",
+    feature = "stm32f358",
+    feature = "stm32f398"
+))]
+use crate::gpio::gpiob::PB12;
 #[cfg(any(
     feature = "stm32f301",
     feature = "stm32f302",
@@ -51,6 +87,7 @@ use crate::rcc::APB1;
 use crate::rcc::APB2;
 use crate::time::Hertz;
 use core::marker::PhantomData;
+use cortex_m::interrupt;
 
 /// SPI error
 #[derive(Debug)]
@@ -74,6 +111,9 @@ pub unsafe trait MisoPin<SPI> {}
 /// MOSI pin -- DO NOT IMPLEMENT THIS TRAIT
 pub unsafe trait MosiPin<SPI> {}
 
+/// NSS pin -- DO NOT IMPLEMENT THIS TRAIT
+pub unsafe trait NssPin<SPI> {}
+
 unsafe impl SckPin<SPI1> for PA5<AF5> {}
 // unsafe impl SckPin<SPI1> for PB3<AF5> {}
 
@@ -108,6 +148,190 @@ unsafe impl MosiPin<SPI2> for PB15<AF5> {}
 unsafe impl MosiPin<SPI3> for PB5<AF6> {}
 unsafe impl MosiPin<SPI3> for PC12<AF6> {}
 
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303",
+    feature = "stm32f328",
+    feature = "stm32f334",
+    feature = "stm32f358",
+    feature = "stm32f373",
+    feature = "stm32f378",
+    feature = "stm32f398"
+))]
+unsafe impl NssPin<SPI1> for PA4<AF5> {}
+// unsafe impl NssPin<SPI1> for PA15<AF5> {}
+
+#[cfg(any(
+    feature = "stm32f301",
+    feature = "stm32f302",
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f318",
+    feature = "stm32f358",
+    feature = "stm32f398"
+))]
+unsafe impl NssPin<SPI2> for PB12<AF5> {}
+
+#[cfg(any(
+    feature = "stm32f301",
+    feature = "stm32f302",
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f318",
+    feature = "stm32f358",
+    feature = "stm32f373",
+    feature = "stm32f378",
+    feature = "stm32f398"
+))]
+unsafe impl NssPin<SPI3> for PA4<AF6> {}
+// unsafe impl NssPin<SPI3> for PA15<AF6> {}
+
+#[cfg(any(
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f398"
+))]
+unsafe impl SckPin<SPI4> for PE2<AF5> {}
+#[cfg(any(
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f398"
+))]
+unsafe impl SckPin<SPI4> for PE12<AF5> {}
+#[cfg(any(
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f398"
+))]
+unsafe impl MisoPin<SPI4> for PE5<AF5> {}
+#[cfg(any(
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f398"
+))]
+unsafe impl MisoPin<SPI4> for PE13<AF5> {}
+#[cfg(any(
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f398"
+))]
+unsafe impl MosiPin<SPI4> for PE6<AF5> {}
+#[cfg(any(
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f398"
+))]
+unsafe impl MosiPin<SPI4> for PE14<AF5> {}
+#[cfg(any(
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f398"
+))]
+unsafe impl NssPin<SPI4> for PE4<AF5> {}
+#[cfg(any(
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f398"
+))]
+unsafe impl NssPin<SPI4> for PE11<AF5> {}
+
+/// Order of the bits in a data frame
+#[derive(Clone, Copy, PartialEq)]
+pub enum BitOrder {
+    /// Most significant bit first
+    MsbFirst,
+    /// Least significant bit first
+    LsbFirst,
+}
+
+/// Format of the data frames
+#[derive(Clone, Copy, PartialEq)]
+pub enum FrameFormat {
+    /// Motorola format, configured by the `Mode` and the bit order
+    Motorola,
+    /// TI format, where the hardware pulses NSS before every frame
+    ///
+    /// The `Mode` and the bit order are ignored.
+    Ti,
+}
+
+/// Placeholder for the MISO pin of a transmit only SPI
+pub struct NoMiso;
+
+/// Placeholder for the MOSI pin of a receive only SPI
+pub struct NoMosi;
+
+/// MISO line of an SPI master, a MISO pin or `NoMiso` -- DO NOT IMPLEMENT THIS TRAIT
+pub unsafe trait MisoLine<SPI> {
+    #[doc(hidden)]
+    const TX_ONLY: bool = false;
+}
+
+unsafe impl<SPI, MISO> MisoLine<SPI> for MISO where MISO: MisoPin<SPI> {}
+
+/// MOSI line of an SPI master, a MOSI pin or `NoMosi` -- DO NOT IMPLEMENT THIS TRAIT
+///
+/// `NoMosi` is only accepted together with a MISO pin `MISO`, as an SPI
+/// without any data line can not be configured.
+pub unsafe trait MosiLine<SPI, MISO> {
+    #[doc(hidden)]
+    const RX_ONLY: bool = false;
+}
+
+unsafe impl<SPI, MISO, MOSI> MosiLine<SPI, MISO> for MOSI where MOSI: MosiPin<SPI> {}
+
+/// Pins of an SPI master, which select its data lines -- DO NOT IMPLEMENT THIS TRAIT
+///
+/// - `(SCK, MISO, MOSI)`: full duplex
+/// - `(SCK, NoMiso, MOSI)`: transmit only
+/// - `(SCK, MISO, NoMosi)`: receive only
+/// - `(SCK, MOSI)`: bidirectional on the single data line MOSI (3-wire)
+///
+/// An NSS pin appended to the first three lets the hardware drive NSS.
+pub unsafe trait Pins<SPI> {
+    #[doc(hidden)]
+    const BIDIMODE: bool;
+    #[doc(hidden)]
+    const RXONLY: bool;
+    #[doc(hidden)]
+    const NSS: bool;
+}
+
+unsafe impl<SPI, SCK, MISO, MOSI> Pins<SPI> for (SCK, MISO, MOSI)
+where
+    SCK: SckPin<SPI>,
+    MISO: MisoLine<SPI>,
+    MOSI: MosiLine<SPI, MISO>,
+{
+    const BIDIMODE: bool = MISO::TX_ONLY;
+    const RXONLY: bool = MOSI::RX_ONLY;
+    const NSS: bool = false;
+}
+
+unsafe impl<SPI, SCK, MOSI> Pins<SPI> for (SCK, MOSI)
+where
+    SCK: SckPin<SPI>,
+    MOSI: MosiPin<SPI>,
+{
+    const BIDIMODE: bool = true;
+    const RXONLY: bool = false;
+    const NSS: bool = false;
+}
+
+unsafe impl<SPI, SCK, MISO, MOSI, NSS> Pins<SPI> for (SCK, MISO, MOSI, NSS)
+where
+    (SCK, MISO, MOSI): Pins<SPI>,
+    NSS: NssPin<SPI>,
+{
+    const BIDIMODE: bool = <(SCK, MISO, MOSI) as Pins<SPI>>::BIDIMODE;
+    const RXONLY: bool = <(SCK, MISO, MOSI) as Pins<SPI>>::RXONLY;
+    const NSS: bool = true;
+}
+
 pub trait Word {
     fn register_config() -> (FRXTH_A, DS_A);
 }
@@ -124,21 +348,279 @@ impl Word for u16 {
     }
 }
 
-/// SPI peripheral operating in full duplex master mode
+/// SPI peripheral operating in master mode
+///
+/// The data lines are selected by the pins, see [`Pins`](trait.Pins.html).
 pub struct Spi<SPI, PINS, WORD = u8> {
     spi: SPI,
     pins: PINS,
     _word: PhantomData<WORD>,
 }
 
+/// SPI peripheral operating in full duplex slave mode
+///
+/// The master selects the slave with the NSS pin, words are only exchanged
+/// while NSS is low.
+pub struct SpiSlave<SPI, PINS, WORD = u8> {
+    spi: SPI,
+    pins: PINS,
+    _word: PhantomData<WORD>,
+}
+
+/// DMA requests of the SPI receiver
+pub struct RxDma<SPI> {
+    _spi: PhantomData<SPI>,
+}
+
+/// DMA requests of the SPI transmitter
+pub struct TxDma<SPI> {
+    _spi: PhantomData<SPI>,
+}
+
+impl<SPI> RxDma<SPI> {
+    pub(crate) fn new() -> Self {
+        RxDma { _spi: PhantomData }
+    }
+}
+
+impl<SPI> TxDma<SPI> {
+    pub(crate) fn new() -> Self {
+        TxDma { _spi: PhantomData }
+    }
+}
+
+/// A full duplex SPI transfer using DMA
+///
+/// Returned by `Spi::transfer_dma`.
+pub struct DmaTransfer<SPI, PINS, WORD, TXB, RXB, TXC, RXC>
+where
+    TXC: dma::Channel,
+    RXC: dma::Channel,
+    TxDma<SPI>: dma::Target,
+    RxDma<SPI>: dma::Target,
+{
+    spi: Spi<SPI, PINS, WORD>,
+    tx: dma::Transfer<TXB, TXC, TxDma<SPI>>,
+    rx: dma::Transfer<RXB, RXC, RxDma<SPI>>,
+}
+
+impl<SPI, PINS, WORD, TXB, RXB, TXC, RXC> DmaTransfer<SPI, PINS, WORD, TXB, RXB, TXC, RXC>
+where
+    TXC: dma::Channel,
+    RXC: dma::Channel,
+    TxDma<SPI>: dma::Target,
+    RxDma<SPI>: dma::Target,
+{
+    /// Is this transfer complete?
+    pub fn is_complete(&self) -> bool {
+        // The last word is received after it has been transmitted
+        self.rx.is_complete()
+    }
+
+    /// Block until this transfer is done and return ownership over its parts
+    ///
+    /// Returns the TX buffer, the RX buffer, the TX channel, the RX channel
+    /// and the SPI.
+    pub fn wait(self) -> (TXB, RXB, TXC, RXC, Spi<SPI, PINS, WORD>) {
+        let (rx_buffer, rx_channel, _) = self.rx.wait();
+        let (tx_buffer, tx_channel, _) = self.tx.wait();
+        (tx_buffer, rx_buffer, tx_channel, rx_channel, self.spi)
+    }
+}
+
+/// A transmit only SPI transfer using DMA
+///
+/// Returned by `Spi::write_dma`. The received data is discarded.
+pub struct DmaWrite<SPI, PINS, WORD, B, C>
+where
+    C: dma::Channel,
+    TxDma<SPI>: dma::Target,
+{
+    spi: Spi<SPI, PINS, WORD>,
+    tx: dma::Transfer<B, C, TxDma<SPI>>,
+}
+
+/// Reads a received word
+fn read_word<WORD>(spi: &spi1::RegisterBlock) -> nb::Result<WORD, Error> {
+    let sr = spi.sr.read();
+
+    Err(if sr.ovr().is_overrun() {
+        nb::Error::Other(Error::Overrun)
+    } else if sr.modf().is_fault() {
+        nb::Error::Other(Error::ModeFault)
+    } else if sr.crcerr().is_no_match() {
+        spi.sr.write(|w| w.crcerr().clear_bit());
+        nb::Error::Other(Error::Crc)
+    } else if sr.rxne().is_not_empty() {
+        let read_ptr = &spi.dr as *const _ as *const WORD;
+        // NOTE(unsafe) read from register owned by the caller
+        let value = unsafe { ptr::read_volatile(read_ptr) };
+        return Ok(value);
+    } else {
+        nb::Error::WouldBlock
+    })
+}
+
+/// Writes a word into the TX FIFO
+fn send_word<WORD>(spi: &spi1::RegisterBlock, word: WORD) -> nb::Result<(), Error> {
+    let sr = spi.sr.read();
+
+    Err(if sr.ovr().is_overrun() {
+        nb::Error::Other(Error::Overrun)
+    } else if sr.modf().is_fault() {
+        nb::Error::Other(Error::ModeFault)
+    } else if sr.crcerr().is_no_match() {
+        spi.sr.write(|w| w.crcerr().clear_bit());
+        nb::Error::Other(Error::Crc)
+    } else if sr.txe().is_empty() {
+        let write_ptr = &spi.dr as *const _ as *mut WORD;
+        // NOTE(unsafe) write to register owned by the caller
+        unsafe { ptr::write_volatile(write_ptr, word) };
+        return Ok(());
+    } else {
+        nb::Error::WouldBlock
+    })
+}
+
+/// Changes the configuration while the SPI is disabled and restores its
+/// state afterwards
+fn reconfigure(spi: &spi1::RegisterBlock, f: impl FnOnce(&spi1::RegisterBlock)) {
+    let enabled = spi.cr1.read().spe().is_enabled();
+    spi.cr1.modify(|_, w| w.spe().disabled());
+    f(spi);
+    spi.cr1.modify(|_, w| w.spe().bit(enabled));
+}
+
+/// Sets DS
+///
+/// # Panics
+///
+/// Panics if the size does not fit the word.
+fn set_frame_size<WORD>(spi: &spi1::RegisterBlock, bits: u8) {
+    let max = 8 * mem::size_of::<WORD>() as u8;
+    assert!(
+        (4..=max).contains(&bits) && bits + 8 > max,
+        "frame size does not fit the word"
+    );
+    reconfigure(spi, |spi| {
+        // NOTE(unsafe) frame sizes of 4 to 16 bits are valid
+        spi.cr2.modify(|r, w| unsafe {
+            w.bits((r.bits() & !(0b1111 << 8)) | (u32::from(bits - 1) << 8))
+        });
+    });
+}
+
+/// Sets LSBFIRST
+fn set_bit_order(spi: &spi1::RegisterBlock, order: BitOrder) {
+    reconfigure(spi, |spi| {
+        spi.cr1.modify(|_, w| match order {
+            BitOrder::MsbFirst => w.lsbfirst().msbfirst(),
+            BitOrder::LsbFirst => w.lsbfirst().lsbfirst(),
+        });
+    });
+}
+
+/// Sets FRF
+fn set_frame_format(spi: &spi1::RegisterBlock, format: FrameFormat) {
+    reconfigure(spi, |spi| {
+        spi.cr2
+            .modify(|_, w| w.frf().bit(format == FrameFormat::Ti));
+    });
+}
+
+/// Sets CRCPR and CRCL and enables the CRC, or disables it with `None`
+fn set_crc<WORD>(spi: &spi1::RegisterBlock, polynomial: Option<u16>) {
+    reconfigure(spi, |spi| {
+        spi.cr1.modify(|_, w| w.crcen().disabled());
+        if let Some(polynomial) = polynomial {
+            // NOTE(unsafe) any polynomial is valid
+            spi.crcpr
+                .write(|w| unsafe { w.bits(u32::from(polynomial)) });
+            // CRCL: 16 bit CRC for u16 words
+            spi.cr1
+                .modify(|_, w| w.crcl().bit(mem::size_of::<WORD>() == 2).crcen().enabled());
+        }
+    });
+}
+
+/// Transmits the words followed by the CRC and replaces them with the
+/// received words, the received CRC is checked
+fn transfer_with_crc<WORD>(spi: &spi1::RegisterBlock, words: &mut [WORD]) -> Result<(), Error>
+where
+    WORD: Copy,
+{
+    assert!(spi.cr1.read().crcen().is_enabled(), "CRC is not enabled");
+
+    // Writing CRCEN resets the CRC calculation
+    reconfigure(spi, |spi| {
+        spi.cr1.modify(|_, w| w.crcen().disabled());
+        spi.cr1.modify(|_, w| w.crcen().enabled());
+    });
+
+    let len = words.len();
+    for (i, word) in words.iter_mut().enumerate() {
+        nb::block!(send_word(spi, *word))?;
+        if i + 1 == len {
+            // CRCNEXT: the CRC follows the last word
+            spi.cr1.modify(|_, w| w.crcnext().set_bit());
+        }
+        *word = nb::block!(read_word(spi))?;
+    }
+
+    // The received CRC is compared by hardware, which sets CRCERR on a mismatch
+    while spi.sr.read().rxne().is_empty() {}
+    // NOTE(unsafe) read from register owned by the caller
+    unsafe { ptr::read_volatile(&spi.dr as *const _ as *const WORD) };
+    if spi.sr.read().crcerr().is_no_match() {
+        spi.sr.write(|w| w.crcerr().clear_bit());
+        return Err(Error::Crc);
+    }
+
+    Ok(())
+}
+
+// `Write` is implemented per word type, as a generic word would overlap
+// with the blanket implementation of embedded-hal over `write::Default`
+macro_rules! write_only {
+    ($SPIX:ident: $($WORD:ty),+) => {
+        $(
+            impl<SCK, MOSI> Write<$WORD> for Spi<$SPIX, (SCK, NoMiso, MOSI), $WORD> {
+                type Error = Error;
+
+                fn write(&mut self, words: &[$WORD]) -> Result<(), Error> {
+                    self.transmit(words)
+                }
+            }
+
+            impl<SCK, MOSI, NSS> Write<$WORD> for Spi<$SPIX, (SCK, NoMiso, MOSI, NSS), $WORD> {
+                type Error = Error;
+
+                fn write(&mut self, words: &[$WORD]) -> Result<(), Error> {
+                    self.transmit(words)
+                }
+            }
+
+            impl<SCK, MOSI> Write<$WORD> for Spi<$SPIX, (SCK, MOSI), $WORD> {
+                type Error = Error;
+
+                fn write(&mut self, words: &[$WORD]) -> Result<(), Error> {
+                    self.transmit(words)
+                }
+            }
+        )+
+    };
+}
+
 macro_rules! hal {
     ($($SPIX:ident: ($spiX:ident, $APBX:ident, $spiXen:ident, $spiXrst:ident, $pclkX:ident),)+) => {
         $(
-            impl<SCK, MISO, MOSI, WORD> Spi<$SPIX, (SCK, MISO, MOSI), WORD> {
-                /// Configures the SPI peripheral to operate in full duplex master mode
+            impl<PINS, WORD> Spi<$SPIX, PINS, WORD> {
+                /// Configures the SPI peripheral to operate in master mode
+                ///
+                /// The pins select the data lines, see [`Pins`](trait.Pins.html).
                 pub fn $spiX<F>(
                     spi: $SPIX,
-                    pins: (SCK, MISO, MOSI),
+                    pins: PINS,
                     mode: Mode,
                     freq: F,
                     clocks: Clocks,
@@ -146,9 +628,7 @@ macro_rules! hal {
                 ) -> Self
                 where
                     F: Into<Hertz>,
-                    SCK: SckPin<$SPIX>,
-                    MISO: MisoPin<$SPIX>,
-                    MOSI: MosiPin<$SPIX>,
+                    PINS: Pins<$SPIX>,
                     WORD: Word,
                 {
                     // enable or reset $SPIX
@@ -160,20 +640,23 @@ macro_rules! hal {
                     spi.cr2.write(|w| {
                         w.frxth().variant(frxth);
                         w.ds().variant(ds);
-                        // Slave Select output disabled
-                        w.ssoe().disabled()
+                        // Slave Select output enabled with hardware NSS
+                        w.ssoe().bit(PINS::NSS)
                     });
 
                     // CPHA: phase
                     // CPOL: polarity
                     // MSTR: master mode
                     // BR: 1 MHz
-                    // SPE: SPI disabled
+                    // SPE: SPI enabled, unless receive only, which starts the clock
                     // LSBFIRST: MSB first
-                    // SSM: enable software slave management (NSS pin free for other uses)
+                    // SSM: software slave management (NSS pin free for other uses),
+                    //      unless hardware NSS
                     // SSI: set nss high = master mode
                     // CRCEN: hardware CRC calculation disabled
-                    // BIDIMODE: 2 line unidirectional (full duplex)
+                    // BIDIMODE: 1 line bidirectional or 2 line unidirectional
+                    // BIDIOE: output enabled
+                    // RXONLY: receive only
                     spi.cr1.write(|w| {
                         w.mstr().master();
 
@@ -190,35 +673,131 @@ macro_rules! hal {
                         w.br().variant(Self::compute_baud_rate(clocks.$pclkX(), freq.into()));
 
                         w.spe()
-                            .enabled()
+                            .bit(!PINS::RXONLY)
                             .lsbfirst()
                             .msbfirst()
                             .ssi()
                             .slave_not_selected()
                             .ssm()
-                            .enabled()
+                            .bit(!PINS::NSS)
                             .crcen()
                             .disabled()
                             .bidimode()
-                            .unidirectional()
+                            .bit(PINS::BIDIMODE)
+                            .bidioe()
+                            .set_bit()
+                            .rxonly()
+                            .bit(PINS::RXONLY)
                     });
 
                     Spi { spi, pins, _word: PhantomData }
                 }
+            }
+
+            impl<SCK, MISO, MOSI, WORD> Spi<$SPIX, (SCK, MISO, MOSI), WORD> {
+                /// Lets the hardware drive the NSS pin, which is low while the
+                /// SPI is enabled
+                ///
+                /// A receive only SPI is only enabled during a reception.
+                ///
+                /// With `pulse`, NSS is pulsed high between consecutive words.
+                ///
+                /// # Panics
+                ///
+                /// Panics if `pulse` is used with `Phase::CaptureOnSecondTransition`.
+                pub fn with_hardware_nss<NSS>(
+                    self,
+                    nss: NSS,
+                    pulse: bool,
+                ) -> Spi<$SPIX, (SCK, MISO, MOSI, NSS), WORD>
+                where
+                    (SCK, MISO, MOSI, NSS): Pins<$SPIX>,
+                {
+                    // NSSP requires CPHA = 0
+                    assert!(
+                        !pulse || self.spi.cr1.read().cpha().bit_is_clear(),
+                        "NSS pulses require capturing on the first transition"
+                    );
 
+                    let enabled = self.spi.cr1.read().spe().is_enabled();
+                    self.spi.cr1.modify(|_, w| w.spe().disabled());
+                    // SSOE: NSS output enabled
+                    // NSSP: NSS pulse between words
+                    self.spi.cr2.modify(|_, w| w.ssoe().enabled().nssp().bit(pulse));
+                    // SSM: NSS is managed by hardware
+                    self.spi.cr1.modify(|_, w| w.ssm().disabled().spe().bit(enabled));
+
+                    let (sck, miso, mosi) = self.pins;
+                    Spi {
+                        spi: self.spi,
+                        pins: (sck, miso, mosi, nss),
+                        _word: PhantomData,
+                    }
+                }
+            }
+
+            impl<PINS, WORD> Spi<$SPIX, PINS, WORD> {
                 /// Releases the SPI peripheral and associated pins
-                pub fn free(self) -> ($SPIX, (SCK, MISO, MOSI)) {
+                pub fn free(self) -> ($SPIX, PINS) {
                     (self.spi, self.pins)
                 }
 
+                /// Sets the size of the data frames in bits
+                ///
+                /// Frames of 4 to 8 bits are `u8` words, frames of 9 to 16 bits
+                /// are `u16` words. This must not be called during a transfer.
+                ///
+                /// # Panics
+                ///
+                /// Panics if the size does not fit the word.
+                pub fn set_frame_size(&mut self, bits: u8) {
+                    set_frame_size::<WORD>(&self.spi, bits);
+                }
+
+                /// Sets the order of the bits in a data frame
+                ///
+                /// This must not be called during a transfer.
+                pub fn set_bit_order(&mut self, order: BitOrder) {
+                    set_bit_order(&self.spi, order);
+                }
+
+                /// Sets the format of the data frames
+                ///
+                /// The TI format requires an NSS pin. This must not be called
+                /// during a transfer.
+                pub fn set_frame_format(&mut self, format: FrameFormat) {
+                    set_frame_format(&self.spi, format);
+                }
+
+                /// Enables the hardware CRC calculation with the polynomial, or
+                /// disables it with `None`
+                ///
+                /// The CRC has the size of the words, 8 bit for `u8` words and
+                /// 16 bit for `u16` words. This must not be called during a
+                /// transfer.
+                pub fn set_crc(&mut self, polynomial: Option<u16>) {
+                    set_crc::<WORD>(&self.spi, polynomial);
+                }
+
+                /// Returns the CRC calculated over the transmitted words
+                pub fn tx_crc(&self) -> u16 {
+                    self.spi.txcrcr.read().bits() as u16
+                }
+
+                /// Returns the CRC calculated over the received words
+                pub fn rx_crc(&self) -> u16 {
+                    self.spi.rxcrcr.read().bits() as u16
+                }
+
                 /// Change the baud rate of the SPI
                 pub fn reclock<F>(&mut self, freq: F, clocks: Clocks)
                     where F: Into<Hertz>
                 {
+                    let enabled = self.spi.cr1.read().spe().is_enabled();
                     self.spi.cr1.modify(|_, w| w.spe().disabled());
                     self.spi.cr1.modify(|_, w| {
                         w.br().variant(Self::compute_baud_rate(clocks.$pclkX(), freq.into()));
-                        w.spe().enabled()
+                        w.spe().bit(enabled)
                     });
                 }
 
@@ -236,54 +815,489 @@ macro_rules! hal {
                         _ => BR_A::DIV256,
                     }
                 }
+            }
+
+            impl<SCK, MISO, MOSI, NSS, WORD> SpiSlave<$SPIX, (SCK, MISO, MOSI, NSS), WORD> {
+                /// Configures the SPI peripheral to operate in full duplex slave mode
+                pub fn $spiX(
+                    spi: $SPIX,
+                    pins: (SCK, MISO, MOSI, NSS),
+                    mode: Mode,
+                    apb2: &mut $APBX,
+                ) -> Self
+                where
+                    SCK: SckPin<$SPIX>,
+                    MISO: MisoPin<$SPIX>,
+                    MOSI: MosiPin<$SPIX>,
+                    NSS: NssPin<$SPIX>,
+                    WORD: Word,
+                {
+                    // enable or reset $SPIX
+                    apb2.enr().modify(|_, w| w.$spiXen().enabled());
+                    apb2.rstr().modify(|_, w| w.$spiXrst().reset());
+                    apb2.rstr().modify(|_, w| w.$spiXrst().clear_bit());
+
+                    let (frxth, ds) = WORD::register_config();
+                    spi.cr2.write(|w| {
+                        w.frxth().variant(frxth);
+                        w.ds().variant(ds);
+                        // Slave Select output disabled
+                        w.ssoe().disabled()
+                    });
+
+                    // CPHA: phase
+                    // CPOL: polarity
+                    // MSTR: slave mode
+                    // SPE: SPI enabled
+                    // LSBFIRST: MSB first
+                    // SSM: disable software slave management (NSS pin selects the slave)
+                    // CRCEN: hardware CRC calculation disabled
+                    // BIDIMODE: 2 line unidirectional (full duplex)
+                    spi.cr1.write(|w| {
+                        w.mstr().slave();
+
+                        match mode.phase {
+                            Phase::CaptureOnFirstTransition => w.cpha().first_edge(),
+                            Phase::CaptureOnSecondTransition => w.cpha().second_edge(),
+                        };
+
+                        match mode.polarity {
+                            Polarity::IdleLow => w.cpol().idle_low(),
+                            Polarity::IdleHigh => w.cpol().idle_high(),
+                        };
+
+                        w.spe()
+                            .enabled()
+                            .lsbfirst()
+                            .msbfirst()
+                            .ssm()
+                            .disabled()
+                            .crcen()
+                            .disabled()
+                            .bidimode()
+                            .unidirectional()
+                    });
+
+                    SpiSlave { spi, pins, _word: PhantomData }
+                }
+            }
+
+            impl<PINS, WORD> SpiSlave<$SPIX, PINS, WORD> {
+                /// Releases the SPI peripheral and associated pins
+                pub fn free(self) -> ($SPIX, PINS) {
+                    (self.spi, self.pins)
+                }
+
+                /// Sets the size of the data frames in bits
+                ///
+                /// Frames of 4 to 8 bits are `u8` words, frames of 9 to 16 bits
+                /// are `u16` words. This must not be called during a transfer.
+                ///
+                /// # Panics
+                ///
+                /// Panics if the size does not fit the word.
+                pub fn set_frame_size(&mut self, bits: u8) {
+                    set_frame_size::<WORD>(&self.spi, bits);
+                }
+
+                /// Sets the order of the bits in a data frame
+                ///
+                /// This must not be called during a transfer.
+                pub fn set_bit_order(&mut self, order: BitOrder) {
+                    set_bit_order(&self.spi, order);
+                }
+
+                /// Sets the format of the data frames
+                ///
+                /// The TI format requires an NSS pin. This must not be called
+                /// during a transfer.
+                pub fn set_frame_format(&mut self, format: FrameFormat) {
+                    set_frame_format(&self.spi, format);
+                }
 
+                /// Enables the hardware CRC calculation with the polynomial, or
+                /// disables it with `None`
+                ///
+                /// The CRC has the size of the words, 8 bit for `u8` words and
+                /// 16 bit for `u16` words. This must not be called during a
+                /// transfer.
+                pub fn set_crc(&mut self, polynomial: Option<u16>) {
+                    set_crc::<WORD>(&self.spi, polynomial);
+                }
+
+                /// Returns the CRC calculated over the transmitted words
+                pub fn tx_crc(&self) -> u16 {
+                    self.spi.txcrcr.read().bits() as u16
+                }
 
+                /// Returns the CRC calculated over the received words
+                pub fn rx_crc(&self) -> u16 {
+                    self.spi.rxcrcr.read().bits() as u16
+                }
+
+                /// Transmits the words followed by the CRC and replaces them
+                /// with the received words
+                ///
+                /// The CRC is calculated over the words of this transfer, a
+                /// mismatch of the received CRC is reported as `Error::Crc`.
+                ///
+                /// # Panics
+                ///
+                /// Panics if the CRC is not enabled with [`set_crc`](#method.set_crc).
+                pub fn transfer_with_crc<'w>(&mut self, words: &'w mut [WORD]) -> Result<&'w [WORD], Error>
+                where
+                    WORD: Copy,
+                {
+                    transfer_with_crc(&self.spi, words)?;
+                    Ok(words)
+                }
             }
 
-            impl<PINS, WORD> FullDuplex<WORD> for Spi<$SPIX, PINS, WORD> {
+            impl<PINS, WORD> FullDuplex<WORD> for SpiSlave<$SPIX, PINS, WORD> {
                 type Error = Error;
 
                 fn read(&mut self) -> nb::Result<WORD, Error> {
-                    let sr = self.spi.sr.read();
-
-                    Err(if sr.ovr().is_overrun() {
-                        nb::Error::Other(Error::Overrun)
-                    } else if sr.modf().is_fault() {
-                        nb::Error::Other(Error::ModeFault)
-                    } else if sr.crcerr().is_no_match() {
-                        nb::Error::Other(Error::Crc)
-                    } else if sr.rxne().is_not_empty() {
-                        let read_ptr = &self.spi.dr as *const _ as *const WORD;
+                    read_word(&self.spi)
+                }
+
+                fn send(&mut self, word: WORD) -> nb::Result<(), Error> {
+                    send_word(&self.spi, word)
+                }
+            }
+
+            impl<PINS, WORD> crate::hal::blocking::spi::transfer::Default<WORD> for SpiSlave<$SPIX, PINS, WORD> {}
+            impl<PINS, WORD> crate::hal::blocking::spi::write::Default<WORD> for SpiSlave<$SPIX, PINS, WORD> {}
+
+            impl dma::Target for RxDma<$SPIX> {
+                fn enable_dma(&mut self) {
+                    // NOTE(unsafe) critical section prevents races
+                    interrupt::free(|_| unsafe {
+                        let cr2 = &(*$SPIX::ptr()).cr2;
+                        cr2.modify(|_, w| w.rxdmaen().set_bit());
+                    });
+                }
+
+                fn disable_dma(&mut self) {
+                    // NOTE(unsafe) critical section prevents races
+                    interrupt::free(|_| unsafe {
+                        let cr2 = &(*$SPIX::ptr()).cr2;
+                        cr2.modify(|_, w| w.rxdmaen().clear_bit());
+                    });
+                }
+            }
+
+            impl dma::Target for TxDma<$SPIX> {
+                fn enable_dma(&mut self) {
+                    // NOTE(unsafe) critical section prevents races
+                    interrupt::free(|_| unsafe {
+                        let cr2 = &(*$SPIX::ptr()).cr2;
+                        cr2.modify(|_, w| w.txdmaen().set_bit());
+                    });
+                }
+
+                fn disable_dma(&mut self) {
+                    // NOTE(unsafe) critical section prevents races
+                    interrupt::free(|_| unsafe {
+                        let cr2 = &(*$SPIX::ptr()).cr2;
+                        cr2.modify(|_, w| w.txdmaen().clear_bit());
+                    });
+                }
+            }
+
+            impl<PINS, WORD> Spi<$SPIX, PINS, WORD> {
+                /// Transmit the TX buffer and receive into the RX buffer at the
+                /// same time using DMA.
+                ///
+                /// # Panics
+                ///
+                /// Panics if the buffers differ in length.
+                pub fn transfer_dma<TXB, RXB, TXC, RXC>(
+                    self,
+                    tx_buffer: TXB,
+                    mut rx_buffer: RXB,
+                    mut tx_channel: TXC,
+                    mut rx_channel: RXC,
+                ) -> DmaTransfer<$SPIX, PINS, WORD, TXB, RXB, TXC, RXC>
+                where
+                    TXB: dma::ReadBuffer<Word = WORD> + 'static,
+                    RXB: dma::WriteBuffer<Word = WORD> + 'static,
+                    TXC: dma::Channel,
+                    RXC: dma::Channel,
+                    TxDma<$SPIX>: dma::OnChannel<TXC>,
+                    RxDma<$SPIX>: dma::OnChannel<RXC>,
+                {
+                    // NOTE(unsafe) only the lengths of the buffers are used here
+                    let (_, tx_len) = unsafe { tx_buffer.read_buffer() };
+                    let (_, rx_len) = unsafe { rx_buffer.write_buffer() };
+                    assert!(tx_len == rx_len, "buffers differ in length");
+
+                    let pa = &self.spi.dr as *const _ as u32;
+                    tx_channel.set_peripheral_address(pa, dma::Increment::Disable);
+                    rx_channel.set_peripheral_address(pa, dma::Increment::Disable);
+
+                    // The receiver has to be ready before the first word is sent
+                    let rx = dma::Transfer::start_write(rx_buffer, rx_channel, RxDma::new());
+                    let tx = dma::Transfer::start_read(tx_buffer, tx_channel, TxDma::new());
+
+                    DmaTransfer { spi: self, tx, rx }
+                }
+
+                /// Transmit the buffer using DMA and discard the received data
+                pub fn write_dma<B, C>(
+                    self,
+                    buffer: B,
+                    mut channel: C,
+                ) -> DmaWrite<$SPIX, PINS, WORD, B, C>
+                where
+                    B: dma::ReadBuffer<Word = WORD> + 'static,
+                    C: dma::Channel,
+                    TxDma<$SPIX>: dma::OnChannel<C>,
+                {
+                    let pa = &self.spi.dr as *const _ as u32;
+                    channel.set_peripheral_address(pa, dma::Increment::Disable);
+
+                    let tx = dma::Transfer::start_read(buffer, channel, TxDma::new());
+
+                    DmaWrite { spi: self, tx }
+                }
+            }
+
+            impl<PINS, WORD, B, C> DmaWrite<$SPIX, PINS, WORD, B, C>
+            where
+                C: dma::Channel,
+            {
+                /// Is this transfer complete?
+                ///
+                /// The last words may still be shifted out, when the DMA is done.
+                pub fn is_complete(&self) -> bool {
+                    self.tx.is_complete()
+                        && self.spi.spi.sr.read().ftlvl().is_empty()
+                        && self.spi.spi.sr.read().bsy().is_not_busy()
+                }
+
+                /// Block until this transfer is done and return ownership over
+                /// the buffer, the channel and the SPI
+                pub fn wait(self) -> (B, C, Spi<$SPIX, PINS, WORD>) {
+                    let (buffer, channel, _) = self.tx.wait();
+                    let spi = self.spi;
+
+                    while !spi.spi.sr.read().ftlvl().is_empty() {}
+                    while spi.spi.sr.read().bsy().is_busy() {}
+
+                    // Discard the received data and clear OVR
+                    while !spi.spi.sr.read().frlvl().is_empty() {
                         // NOTE(unsafe) read from register owned by this Spi struct
-                        let value = unsafe { ptr::read_volatile(read_ptr) };
-                        return Ok(value);
-                    } else {
-                        nb::Error::WouldBlock
-                    })
+                        unsafe { ptr::read_volatile(&spi.spi.dr as *const _ as *const WORD) };
+                    }
+                    spi.spi.sr.read();
+
+                    (buffer, channel, spi)
+                }
+            }
+
+            impl<SCK, MISO, MOSI, WORD> Spi<$SPIX, (SCK, MISO, MOSI), WORD>
+            where
+                MISO: MisoPin<$SPIX>,
+                MOSI: MosiPin<$SPIX>,
+            {
+                /// Transmits the words followed by the CRC and replaces them
+                /// with the received words
+                ///
+                /// The CRC is calculated over the words of this transfer, a
+                /// mismatch of the received CRC is reported as `Error::Crc`.
+                ///
+                /// # Panics
+                ///
+                /// Panics if the CRC is not enabled with [`set_crc`](#method.set_crc).
+                pub fn transfer_with_crc<'w>(&mut self, words: &'w mut [WORD]) -> Result<&'w [WORD], Error>
+                where
+                    WORD: Copy,
+                {
+                    transfer_with_crc(&self.spi, words)?;
+                    Ok(words)
+                }
+            }
+
+            impl<SCK, MISO, MOSI, NSS, WORD> Spi<$SPIX, (SCK, MISO, MOSI, NSS), WORD>
+            where
+                MISO: MisoPin<$SPIX>,
+                MOSI: MosiPin<$SPIX>,
+            {
+                /// Transmits the words followed by the CRC and replaces them
+                /// with the received words
+                ///
+                /// The CRC is calculated over the words of this transfer, a
+                /// mismatch of the received CRC is reported as `Error::Crc`.
+                ///
+                /// # Panics
+                ///
+                /// Panics if the CRC is not enabled with [`set_crc`](#method.set_crc).
+                pub fn transfer_with_crc<'w>(&mut self, words: &'w mut [WORD]) -> Result<&'w [WORD], Error>
+                where
+                    WORD: Copy,
+                {
+                    transfer_with_crc(&self.spi, words)?;
+                    Ok(words)
+                }
+            }
+
+            impl<SCK, MISO, MOSI, WORD> FullDuplex<WORD> for Spi<$SPIX, (SCK, MISO, MOSI), WORD>
+            where
+                MISO: MisoPin<$SPIX>,
+                MOSI: MosiPin<$SPIX>,
+            {
+                type Error = Error;
+
+                fn read(&mut self) -> nb::Result<WORD, Error> {
+                    read_word(&self.spi)
                 }
 
                 fn send(&mut self, word: WORD) -> nb::Result<(), Error> {
-                    let sr = self.spi.sr.read();
-
-                    Err(if sr.ovr().is_overrun() {
-                        nb::Error::Other(Error::Overrun)
-                    } else if sr.modf().is_fault() {
-                        nb::Error::Other(Error::ModeFault)
-                    } else if sr.crcerr().is_no_match() {
-                        nb::Error::Other(Error::Crc)
-                    } else if sr.txe().is_empty() {
-                        let write_ptr = &self.spi.dr as *const _ as *mut WORD;
-                        // NOTE(unsafe) write to register owned by this Spi struct
-                        unsafe { ptr::write_volatile(write_ptr, word) };
-                        return Ok(());
-                    } else {
-                        nb::Error::WouldBlock
-                    })
+                    send_word(&self.spi, word)
                 }
             }
 
-            impl<PINS, WORD> crate::hal::blocking::spi::transfer::Default<WORD> for Spi<$SPIX, PINS, WORD> {}
-            impl<PINS, WORD> crate::hal::blocking::spi::write::Default<WORD> for Spi<$SPIX, PINS, WORD> {}
+            impl<SCK, MISO, MOSI, NSS, WORD> FullDuplex<WORD> for Spi<$SPIX, (SCK, MISO, MOSI, NSS), WORD>
+            where
+                MISO: MisoPin<$SPIX>,
+                MOSI: MosiPin<$SPIX>,
+            {
+                type Error = Error;
+
+                fn read(&mut self) -> nb::Result<WORD, Error> {
+                    read_word(&self.spi)
+                }
+
+                fn send(&mut self, word: WORD) -> nb::Result<(), Error> {
+                    send_word(&self.spi, word)
+                }
+            }
+
+            impl<SCK, MISO, MOSI, WORD> crate::hal::blocking::spi::transfer::Default<WORD> for Spi<$SPIX, (SCK, MISO, MOSI), WORD>
+            where
+                MISO: MisoPin<$SPIX>,
+                MOSI: MosiPin<$SPIX>,
+            {}
+            impl<SCK, MISO, MOSI, WORD> crate::hal::blocking::spi::write::Default<WORD> for Spi<$SPIX, (SCK, MISO, MOSI), WORD>
+            where
+                MISO: MisoPin<$SPIX>,
+                MOSI: MosiPin<$SPIX>,
+            {}
+            impl<SCK, MISO, MOSI, NSS, WORD> crate::hal::blocking::spi::transfer::Default<WORD> for Spi<$SPIX, (SCK, MISO, MOSI, NSS), WORD>
+            where
+                MISO: MisoPin<$SPIX>,
+                MOSI: MosiPin<$SPIX>,
+            {}
+            impl<SCK, MISO, MOSI, NSS, WORD> crate::hal::blocking::spi::write::Default<WORD> for Spi<$SPIX, (SCK, MISO, MOSI, NSS), WORD>
+            where
+                MISO: MisoPin<$SPIX>,
+                MOSI: MosiPin<$SPIX>,
+            {}
+
+            unsafe impl MisoLine<$SPIX> for NoMiso {
+                const TX_ONLY: bool = true;
+            }
+
+            unsafe impl<MISO> MosiLine<$SPIX, MISO> for NoMosi
+            where
+                MISO: MisoPin<$SPIX>,
+            {
+                const RX_ONLY: bool = true;
+            }
+
+            write_only!($SPIX: u8, u16);
+
+            impl<SCK, MISO, WORD> Spi<$SPIX, (SCK, MISO, NoMosi), WORD> {
+                /// Fills the buffer with received words
+                pub fn read(&mut self, buffer: &mut [WORD]) -> Result<(), Error> {
+                    self.receive(buffer)
+                }
+            }
+
+            impl<SCK, MISO, NSS, WORD> Spi<$SPIX, (SCK, MISO, NoMosi, NSS), WORD> {
+                /// Fills the buffer with received words
+                pub fn read(&mut self, buffer: &mut [WORD]) -> Result<(), Error> {
+                    self.receive(buffer)
+                }
+            }
+
+            impl<SCK, MOSI, WORD> Spi<$SPIX, (SCK, MOSI), WORD> {
+                /// Fills the buffer with words received on the data line
+                pub fn read(&mut self, buffer: &mut [WORD]) -> Result<(), Error> {
+                    // Wait until the last word is sent
+                    while !self.spi.sr.read().ftlvl().is_empty() {}
+                    while self.spi.sr.read().bsy().is_busy() {}
+
+                    // BIDIOE: output disabled
+                    self.spi.cr1.modify(|_, w| w.spe().disabled());
+                    self.spi.cr1.modify(|_, w| w.bidioe().clear_bit());
+
+                    let result = self.receive(buffer);
+
+                    self.spi.cr1.modify(|_, w| w.bidioe().set_bit());
+                    self.spi.cr1.modify(|_, w| w.spe().enabled());
+                    result
+                }
+            }
+
+            impl<PINS, WORD> Spi<$SPIX, PINS, WORD> {
+                /// Sends the words and waits until the last one is sent
+                fn transmit(&mut self, words: &[WORD]) -> Result<(), Error>
+                where
+                    WORD: Copy,
+                {
+                    for word in words {
+                        nb::block!(send_word(&self.spi, *word))?;
+                    }
+
+                    while !self.spi.sr.read().ftlvl().is_empty() {}
+                    while self.spi.sr.read().bsy().is_busy() {}
+
+                    Ok(())
+                }
+
+                /// Receives words, while the SPI is disabled otherwise
+                ///
+                /// A receiving master generates the clock as long as it is
+                /// enabled, so the SPI is disabled during the last word.
+                fn receive(&mut self, buffer: &mut [WORD]) -> Result<(), Error> {
+                    let (last, words) = match buffer.split_last_mut() {
+                        Some(split) => split,
+                        None => return Ok(()),
+                    };
+
+                    self.spi.cr1.modify(|_, w| w.spe().enabled());
+                    for word in words {
+                        match nb::block!(read_word(&self.spi)) {
+                            Ok(value) => *word = value,
+                            Err(error) => {
+                                self.stop_receive();
+                                return Err(error);
+                            }
+                        }
+                    }
+
+                    self.spi.cr1.modify(|_, w| w.spe().disabled());
+                    while self.spi.sr.read().bsy().is_busy() {}
+                    let result = nb::block!(read_word(&self.spi)).map(|value| *last = value);
+
+                    self.stop_receive();
+                    result
+                }
+
+                /// Disables the SPI and discards the words received meanwhile
+                fn stop_receive(&mut self) {
+                    self.spi.cr1.modify(|_, w| w.spe().disabled());
+                    while self.spi.sr.read().bsy().is_busy() {}
+
+                    while !self.spi.sr.read().frlvl().is_empty() {
+                        // NOTE(unsafe) read from register owned by this Spi struct
+                        unsafe { ptr::read_volatile(&self.spi.dr as *const _ as *const WORD) };
+                    }
+                    // Clear OVR
+                    self.spi.sr.read();
+                }
+            }
         )+
     }
 }
@@ -314,6 +1328,15 @@ hal! {
     SPI3: (spi3, APB1, spi3en, spi3rst, pclk1),
 }
 
+#[cfg(any(
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f398"
+))]
+hal! {
+    SPI4: (spi4, APB2, spi4en, spi4rst, pclk2),
+}
+
 // FIXME not working
 // TODO measure if this actually faster than the default implementation
 // impl ::hal::blocking::spi::Write<u8> for Spi {
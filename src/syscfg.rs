@@ -0,0 +1,116 @@
+//! System configuration controller
+
+use crate::pac::{syscfg, SYSCFG};
+use crate::rcc::APB2;
+
+/// Extension trait that constrains the `SYSCFG` peripheral
+pub trait SysCfgExt {
+    /// Constrains the `SYSCFG` peripheral so it plays nicely with the other abstractions
+    fn constrain(self, apb2: &mut APB2) -> SysCfg;
+}
+
+impl SysCfgExt for SYSCFG {
+    fn constrain(self, apb2: &mut APB2) -> SysCfg {
+        apb2.enr().modify(|_, w| w.syscfgen().set_bit());
+
+        SysCfg { _0: () }
+    }
+}
+
+/// Remapping of DMA requests to DMA1
+///
+/// On devices without DMA2, the requests of these peripherals are only
+/// served by DMA1, if they are remapped.
+#[cfg(any(
+    feature = "stm32f301",
+    feature = "stm32f318",
+    feature = "stm32f303x6",
+    feature = "stm32f303x8",
+    feature = "stm32f334",
+    feature = "stm32f328"
+))]
+#[derive(Clone, Copy, PartialEq)]
+pub enum DmaRemap {
+    /// TIM6 update and DAC1 channel 1 on DMA1 channel 3
+    Tim6Dac1Ch1,
+    /// TIM7 update and DAC1 channel 2 on DMA1 channel 4
+    Tim7Dac1Ch2,
+    /// DAC2 channel 1 on DMA1 channel 5
+    #[cfg(any(
+        feature = "stm32f303x6",
+        feature = "stm32f303x8",
+        feature = "stm32f334",
+        feature = "stm32f328"
+    ))]
+    Dac2Ch1,
+}
+
+/// Constrained SYSCFG peripheral
+///
+/// An instance of this struct is aquired by calling the
+/// [constrain](trait.SysCfgExt.html#tymethod.constrain) function on the
+/// [pac::SYSCFG](../pac/struct.SYSCFG.html) struct.
+///
+/// ```
+/// let dp = pac::Peripherals::take().unwrap();
+/// let mut rcc = dp.RCC.constrain();
+/// let mut syscfg = dp.SYSCFG.constrain(&mut rcc.apb2);
+/// ```
+pub struct SysCfg {
+    _0: (),
+}
+
+impl SysCfg {
+    /// Remaps DMA requests to DMA1 or restores their default mapping
+    #[cfg(any(
+        feature = "stm32f301",
+        feature = "stm32f318",
+        feature = "stm32f303x6",
+        feature = "stm32f303x8",
+        feature = "stm32f334",
+        feature = "stm32f328"
+    ))]
+    pub fn remap_dma(&mut self, remap: DmaRemap, enable: bool) {
+        // TIM6_DAC1_DMA_RMP, TIM7_DAC2_DMA_RMP and DAC2_CH1_DMA_RMP
+        let bit = match remap {
+            DmaRemap::Tim6Dac1Ch1 => 13,
+            DmaRemap::Tim7Dac1Ch2 => 14,
+            #[cfg(any(
+                feature = "stm32f303x6",
+                feature = "stm32f303x8",
+                feature = "stm32f334",
+                feature = "stm32f328"
+            ))]
+            DmaRemap::Dac2Ch1 => 15,
+        };
+        // NOTE(unsafe) only the remap bit is modified
+        self.cfgr1().modify(|r, w| unsafe {
+            w.bits((r.bits() & !(1 << bit)) | (u32::from(enable) << bit))
+        });
+    }
+
+    pub(crate) fn cfgr1(&mut self) -> &syscfg::CFGR1 {
+        // NOTE(unsafe) this proxy grants exclusive access to this register
+        unsafe { &(*SYSCFG::ptr()).cfgr1 }
+    }
+
+    pub(crate) fn exticr1(&mut self) -> &syscfg::EXTICR1 {
+        // NOTE(unsafe) this proxy grants exclusive access to this register
+        unsafe { &(*SYSCFG::ptr()).exticr1 }
+    }
+
+    pub(crate) fn exticr2(&mut self) -> &syscfg::EXTICR2 {
+        // NOTE(unsafe) this proxy grants exclusive access to this register
+        unsafe { &(*SYSCFG::ptr()).exticr2 }
+    }
+
+    pub(crate) fn exticr3(&mut self) -> &syscfg::EXTICR3 {
+        // NOTE(unsafe) this proxy grants exclusive access to this register
+        unsafe { &(*SYSCFG::ptr()).exticr3 }
+    }
+
+    pub(crate) fn exticr4(&mut self) -> &syscfg::EXTICR4 {
+        // NOTE(unsafe) this proxy grants exclusive access to this register
+        unsafe { &(*SYSCFG::ptr()).exticr4 }
+    }
+}
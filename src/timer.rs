@@ -52,6 +52,7 @@ use crate::pac::{TIM3, TIM7};
 use cast::{u16, u32};
 use void::Void;
 
+use crate::dma;
 use crate::rcc::{Clocks, APB1, APB2};
 use crate::time::Hertz;
 
@@ -152,14 +153,14 @@ macro_rules! hal {
                 /// Starts listening for an `event`
                 pub fn listen(&mut self, event: Event) {
                     match event {
-                        Event::Update => self.tim.dier.write(|w| w.uie().enabled()),
+                        Event::Update => self.tim.dier.modify(|_, w| w.uie().enabled()),
                     }
                 }
 
                 /// Stops listening for an `event`
                 pub fn unlisten(&mut self, event: Event) {
                     match event {
-                        Event::Update => self.tim.dier.write(|w| w.uie().disabled()),
+                        Event::Update => self.tim.dier.modify(|_, w| w.uie().disabled()),
                     }
                 }
 
@@ -179,6 +180,17 @@ macro_rules! hal {
                     self.tim
                 }
             }
+
+            /// DMA requests on update events
+            impl dma::Target for Timer<$TIMX> {
+                fn enable_dma(&mut self) {
+                    self.tim.dier.modify(|_, w| w.ude().set_bit());
+                }
+
+                fn disable_dma(&mut self) {
+                    self.tim.dier.modify(|_, w| w.ude().clear_bit());
+                }
+            }
         )+
     }
 }